@@ -1,22 +1,63 @@
 #![feature(portable_simd)]
 use std::time::SystemTime;
 
+use board::Board;
 use chess_for_crabs::*;
-use eval::MaterialCount;
-use game::Game;
-use search::IDAB;
+use search::{SearchOptions, IDAB};
 
-fn main() {
-    let game = Game::new();
-    let mut search = IDAB::new(MaterialCount());
+const DEPTH: u64 = 6;
+
+// The start position and the openings the match runner plays from, so that no single
+// position can make an option look better or worse than it is
+fn positions() -> Vec<Board> {
+    let openings = std::fs::read_to_string("games/openings.fen").unwrap_or_default();
+    let openings = openings
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| fen::parse(line).unwrap().board);
+    std::iter::once(Board::initial()).chain(openings).collect()
+}
+
+fn bench(name: &str, positions: &[Board], options: SearchOptions) -> i64 {
     let start = SystemTime::now();
-    let eval = search.evaluate(game.board, game.board.player, 8, i64::min_value(), i64::max_value());
-    std::hint::black_box(eval);
+    let mut nodes = 0;
+    for pos in positions {
+        let mut search = IDAB::new(eval::by_name(name).unwrap());
+        search.options = options;
+        let eval = search.evaluate(*pos, pos.player, DEPTH, i64::MIN, i64::MAX);
+        std::hint::black_box(eval);
+        nodes += search.searched_positions;
+    }
     let end = SystemTime::now();
     let delta = end.duration_since(start).unwrap();
     println!(
-        "Evaluated {} positions in {} milliseconds",
-        search.searched_positions,
+        "Evaluated {nodes} positions in {} milliseconds",
         delta.as_millis()
     );
+    nodes
+}
+
+fn main() {
+    let name = std::env::args().nth(1);
+    let name = name.as_deref().unwrap_or(eval::DEFAULT_EVALUATOR);
+    if eval::by_name(name).is_none() {
+        return println!(
+            "Unknown evaluator {name}, try one of {}",
+            eval::EVALUATORS.join(", ")
+        );
+    }
+    let positions = positions();
+    println!("Searching {} positions to depth {DEPTH}", positions.len());
+    let nodes = bench(name, &positions, SearchOptions::default());
+    // Turn each option off on its own, to see what it's worth
+    for option in search::SEARCH_OPTIONS {
+        print!("Without {option}: ");
+        let mut options = SearchOptions::default();
+        options.set(option, false).unwrap();
+        let without = bench(name, &positions, options);
+        println!(
+            "{option} saves {:.1}% of the nodes",
+            100.0 * (without - nodes) as f64 / without as f64
+        );
+    }
 }
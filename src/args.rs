@@ -1,23 +1,63 @@
 pub enum Args {
     Interactive,
     FEN(String),
+    UCI,
+    Perft { depth: u64, fen: Option<String> },
+}
+// What to run, plus the options that go with any of it
+pub struct Options {
+    pub args: Args,
+    pub eval: String,
 }
 pub fn print_usage() {
     let exec = std::env::args().next().unwrap();
-    println!("Usage: {exec} (--fen [FEN])?")
+    println!(
+        "Usage: {exec} (--eval [EVAL])? (--fen [FEN] | --uci | perft [DEPTH] (--fen [FEN])?)?"
+    );
+    println!(
+        "EVAL is one of {}, or several joined with +, each with an optional :PERCENT weight",
+        crate::eval::EVALUATORS.join(", ")
+    )
 }
 
 // TODO: we will eventually want an actual proper system for this.
 fn unexpected_arg(invalid: String) {
     println!("Invalid argument {invalid}");
 }
-impl Args {
+impl Options {
     pub fn parse() -> Option<Self> {
-        let mut args = std::env::args();
+        let mut args: Vec<String> = std::env::args().collect();
+        let mut eval = crate::eval::DEFAULT_EVALUATOR.to_string();
+        if let Some(pos) = args.iter().position(|arg| arg == "--eval") {
+            args.remove(pos);
+            if pos == args.len() {
+                return None;
+            }
+            eval = args.remove(pos);
+        }
+        let args = Args::parse(args.into_iter())?;
+        Some(Options { args, eval })
+    }
+}
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Option<Self> {
         let _exec = args.next()?;
         if let Some(arg) = args.next() {
             match arg.as_str() {
                 "--fen" => Some(Args::FEN(args.next()?)),
+                "--uci" => Some(Args::UCI),
+                "perft" => {
+                    let depth = args.next()?.parse().ok()?;
+                    let fen = match args.next() {
+                        Some(arg) if arg == "--fen" => Some(args.next()?),
+                        Some(arg) => {
+                            unexpected_arg(arg);
+                            return None;
+                        }
+                        None => None,
+                    };
+                    Some(Args::Perft { depth, fen })
+                }
                 _incorrect => {
                     unexpected_arg(arg);
                     None
@@ -1,17 +1,21 @@
 #![feature(portable_simd)]
 use std::io::{BufRead, Write};
+use std::time::Duration;
 
-use args::{print_usage, Args};
+use args::{print_usage, Args, Options};
+use board::Board;
 use chess_for_crabs::{moves::Move, *};
-use eval::MaterialCount;
+use eval::Weighted;
 use fen;
 use game::Game;
-use moves::AlgebraicMove;
+use moves::{AlgebraicMove, CoordinateMove};
 use piece::Piece;
-use search::IDAB;
+use search::{SearchLimits, IDAB};
 
 use piece::Piece::*;
 
+const THINKING_TIME_SECS: u64 = 5;
+
 fn try_read<T, F: Fn(&str) -> Result<T, &str>>(
     buffer: &mut String,
     parse: F,
@@ -35,15 +39,26 @@ fn try_read<T, F: Fn(&str) -> Result<T, &str>>(
 
 enum Command {
     Move(AlgebraicMove),
+    CoordinateMove(CoordinateMove),
     Quit,
     ShowMoves(Piece),
     Eval,
+    Trace,
+    Exchange(String),
     Undo,
+    Save(String),
 }
 impl Command {
     fn parse(s: &str) -> Result<Command, &str> {
-        Ok(if let Some(alg) = AlgebraicMove::parse(s) {
+        // e2e4 is also valid SAN, if a bit verbose, but e1g1 isn't
+        Ok(if let Some(mv) = CoordinateMove::parse(s) {
+            Command::CoordinateMove(mv)
+        } else if let Some(alg) = AlgebraicMove::parse(s) {
             Command::Move(alg)
+        } else if let Some(path) = s.strip_prefix(":s ") {
+            Command::Save(path.trim().to_string())
+        } else if let Some(mv) = s.strip_prefix(":x ") {
+            Command::Exchange(mv.trim().to_string())
         } else {
             match s.as_bytes() {
                 [b':', b'q'] => Command::Quit,
@@ -55,6 +70,7 @@ impl Command {
                     }
                 }
                 [b':', b'e'] => Command::Eval,
+                [b':', b't'] => Command::Trace,
                 [b':', b'u'] => Command::Undo,
                 _ => return Err("I cannot parse that"),
             }
@@ -62,27 +78,41 @@ impl Command {
     }
 }
 
+fn san_line(board: &Board, pv: &[Move]) -> Vec<String> {
+    let mut board = *board;
+    let mut line = Vec::with_capacity(pv.len());
+    for mv in pv {
+        if let Some(alg) = board.to_algebraic(*mv) {
+            line.push(alg.to_string())
+        }
+        board = board.apply(mv)
+    }
+    line
+}
+
 fn display(game: &Game) {
     println!("{}\n", game.board.fen());
     let stdout = std::io::stdout();
     let mut out = stdout.lock();
     game.board.display(&mut out).unwrap();
-    for color in piece::Color::list() {
-        if (game.board[King] & game.board[color.opponent()]).is_empty() {
-            println!("{color} wins!");
-            return;
-        }
+    if game.board.is_checkmate() {
+        println!("Checkmate, {} wins!", game.board.player.opponent());
+    } else if let Some(draw) = game.draw() {
+        println!("Draw by {draw}!");
     }
 }
 
-fn play_from(mut game: Game) {
-    let mut search = IDAB::new(MaterialCount());
+fn play_from(mut game: Game, evaluator: Weighted) {
+    let mut search = IDAB::new(evaluator);
     let mut buffer = String::new();
     display(&game);
 
     loop {
         let cmd = try_read(&mut buffer, Command::parse).unwrap();
         match cmd {
+            Command::Move(_) | Command::CoordinateMove(_) if game.is_over() => {
+                println!("The game is over")
+            }
             Command::Move(alg) => match game.board.is_legal(&alg) {
                 Ok(mv) => {
                     game.make_move(&alg, &mv);
@@ -91,13 +121,26 @@ fn play_from(mut game: Game) {
                 }
                 Err(err) => println!("{}", err.as_str()),
             },
+            Command::CoordinateMove(coords) => match game.board.from_coordinate(&coords) {
+                Some(mv) => {
+                    let alg = game.board.to_algebraic(mv).unwrap();
+                    game.make_move(&alg, &mv);
+                    println!("{}", game.log);
+                    display(&game)
+                }
+                None => println!("Illegal move"),
+            },
             Command::Quit => return,
             Command::ShowMoves(piece) => {
                 let mut moves = Vec::with_capacity(32);
-                game.board.piece_moves(piece, &mut moves);
+                game.board.legal_moves(&mut moves);
                 for mv in moves {
                     if let Some(alg) = game.board.to_algebraic(mv) {
-                        print!("{alg}, ")
+                        if matches!(alg, AlgebraicMove::Simple(alg) if alg.piece == piece)
+                            || (piece == King && !matches!(alg, AlgebraicMove::Simple(_)))
+                        {
+                            print!("{alg}, ")
+                        }
                     } else {
                         println!("\nNon-algebraic move found");
                         println!("{:?}", mv);
@@ -105,8 +148,55 @@ fn play_from(mut game: Game) {
                 }
             }
             Command::Eval => {
-                let evaluation = search.evaluate(game.board, game.board.player, 6, 0, 0);
-                println!("{evaluation}");
+                let limits = SearchLimits::movetime(Duration::from_secs(THINKING_TIME_SECS));
+                search.history = game.history.clone();
+                let result = search.iterate(game.board, &limits, |result, elapsed| {
+                    println!(
+                        "depth {} ({} ms): {} {}",
+                        result.depth,
+                        elapsed.as_millis(),
+                        result.score,
+                        san_line(&game.board, &result.pv).join(" ")
+                    )
+                });
+                println!("{}", result.score);
+                if let Some(best) = san_line(&game.board, &result.pv).first() {
+                    println!("Best move: {best}");
+                }
+            }
+            Command::Trace => println!("{}", search.evaluator.trace(&game.board)),
+            Command::Exchange(mv) => {
+                let board = &game.board;
+                let legal = match CoordinateMove::parse(&mv) {
+                    Some(coords) => board.from_coordinate(&coords),
+                    None => AlgebraicMove::parse(&mv).and_then(|alg| board.is_legal(&alg).ok()),
+                };
+                let Some(legal) = legal else {
+                    println!("Illegal move");
+                    continue;
+                };
+                let alg = board.to_algebraic(legal).unwrap();
+                // Moving somewhere the piece can just be taken loses material too
+                let square = board.to_coordinate(legal).dst;
+                match board.see(&legal) {
+                    0 => {
+                        println!("{alg} is safe, it comes out even after the exchanges on {square}")
+                    }
+                    gain if gain > 0 => {
+                        println!("{alg} is safe, it wins {gain} after the exchanges on {square}")
+                    }
+                    loss => println!(
+                        "{alg} is not safe, it loses {} after the exchanges on {square}",
+                        -loss
+                    ),
+                }
+            }
+            Command::Save(path) => {
+                let pgn = pgn::write(&game, &[], game.result());
+                match std::fs::write(&path, pgn) {
+                    Ok(()) => println!("Saved to {path}"),
+                    Err(err) => println!("Could not save to {path}: {err}"),
+                }
             }
             Command::Undo => {
                 game.undo_last_move();
@@ -117,7 +207,7 @@ fn play_from(mut game: Game) {
     }
 }
 
-fn play() {
+fn play(evaluator: Weighted) {
     let mut buffer = String::new();
 
     println!("(1) New game");
@@ -141,7 +231,7 @@ fn play() {
         }
         _ => unreachable!(),
     };
-    play_from(game)
+    play_from(game, evaluator)
 }
 
 fn debug_to(target: Game) {
@@ -162,13 +252,45 @@ fn debug_to(target: Game) {
     }
 }
 
+fn perft(depth: u64, fen: Option<String>) {
+    let board = match fen {
+        Some(fen) => match fen::parse(&fen) {
+            Some(game) => game.board,
+            None => return println!("Invalid FEN"),
+        },
+        None => Board::initial(),
+    };
+    let start = std::time::Instant::now();
+    let mut total = 0;
+    for (mv, nodes) in board.divide(depth) {
+        println!("{}: {nodes}", board.to_coordinate(mv));
+        total += nodes;
+    }
+    // Which is nothing at depth 0, where the position itself is the one node
+    if depth == 0 {
+        total = board.perft(0);
+    }
+    let elapsed = start.elapsed().as_millis();
+    println!("\nNodes searched: {total} ({elapsed} ms)");
+}
+
 fn main() {
-    match Args::parse() {
-        Some(Args::Interactive) => play(),
-        Some(Args::FEN(fen)) => match fen::parse(&fen) {
-            Some(game) => play_from(game),
+    let Some(Options { args, eval }) = Options::parse() else {
+        return print_usage();
+    };
+    let Some(evaluator) = eval::by_name(&eval) else {
+        println!("Unknown evaluator {eval}");
+        return print_usage();
+    };
+    match args {
+        Args::Interactive => play(evaluator),
+        Args::FEN(fen) => match fen::parse(&fen) {
+            Some(game) => play_from(game, evaluator),
             None => println!("Invalid FEN"),
         },
-        None => print_usage(),
+        Args::UCI => uci::Uci::with_evaluator(evaluator)
+            .run(std::io::stdin().lock())
+            .unwrap(),
+        Args::Perft { depth, fen } => perft(depth, fen),
     }
 }
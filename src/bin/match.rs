@@ -0,0 +1,388 @@
+use std::time::{Duration, Instant};
+
+use board::Board;
+use chess_for_crabs::*;
+use eval::Weighted;
+use game::Game;
+use pgn::GameResult;
+use piece::Color;
+use search::{SearchLimits, SearchOptions, IDAB};
+
+const DEFAULT_GAMES: usize = 2;
+const DEFAULT_PGN: &str = "match.pgn";
+// Nobody is winning a game that gets this long, call it a draw
+const DEFAULT_MAX_MOVES: u64 = 200;
+
+fn print_usage() {
+    let exec = std::env::args().next().unwrap();
+    println!(
+        "Usage: {exec} ENGINE ENGINE [--games N] [--openings FILE] [--pgn FILE] [--max-moves N]"
+    );
+    println!("  ENGINE: EVAL[,depth=N][,nodes=N][,movetime=MS][,tc=SECS+INC][,no=OPTION]...");
+    println!(
+        "  EVAL: one of {}, or several joined with +",
+        eval::EVALUATORS.join(", ")
+    );
+    println!(
+        "  OPTION: search feature to turn off, one of {}",
+        search::SEARCH_OPTIONS.join(", ")
+    );
+    println!(
+        "  FILE (openings): one FEN per line, each played with both colours (games/openings.fen)"
+    );
+}
+
+struct Engine {
+    name: String,
+    search: IDAB<Weighted>,
+    limits: SearchLimits,
+    // Base time and increment, if the engine plays on a clock
+    clock: Option<(Duration, Duration)>,
+}
+
+impl Engine {
+    fn parse(spec: &str) -> Result<Engine, String> {
+        let mut parts = spec.split(',');
+        let name = parts.next().unwrap_or_default();
+        let evaluator = eval::by_name(name).ok_or(format!("Unknown evaluator {name}"))?;
+        let mut limits = SearchLimits::default();
+        let mut clock = None;
+        let mut options = SearchOptions::default();
+        for part in parts {
+            let invalid = || format!("Invalid engine option {part}");
+            let (key, value) = part.split_once('=').ok_or_else(invalid)?;
+            let number = || value.parse::<u64>().map_err(|_| invalid());
+            match key {
+                "depth" => limits.depth = Some(number()?),
+                "nodes" => limits.nodes = Some(number()? as i64),
+                "movetime" => limits.movetime = Some(Duration::from_millis(number()?)),
+                "no" => options.set(value, false).ok_or_else(invalid)?,
+                "tc" => {
+                    let (base, inc) = value.split_once('+').unwrap_or((value, "0"));
+                    let secs = |s: &str| s.parse::<f64>().ok().filter(|s| *s >= 0.0);
+                    let (Some(base), Some(inc)) = (secs(base), secs(inc)) else {
+                        return Err(invalid());
+                    };
+                    clock = Some((Duration::from_secs_f64(base), Duration::from_secs_f64(inc)))
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        let unlimited = limits.depth.is_none() && limits.nodes.is_none();
+        if unlimited && limits.movetime.is_none() && clock.is_none() {
+            return Err(format!("{spec} needs a depth, node count or time control"));
+        }
+        let mut search = IDAB::new(evaluator);
+        search.options = options;
+        Ok(Engine {
+            name: spec.to_string(),
+            search,
+            limits,
+            clock,
+        })
+    }
+}
+
+struct Options {
+    engines: [String; 2],
+    games: usize,
+    openings: Option<String>,
+    pgn: String,
+    max_moves: u64,
+}
+
+impl Options {
+    fn parse() -> Option<Options> {
+        let mut args = std::env::args().skip(1);
+        let mut engines = Vec::new();
+        let mut options = Options {
+            engines: Default::default(),
+            games: DEFAULT_GAMES,
+            openings: None,
+            pgn: DEFAULT_PGN.to_string(),
+            max_moves: DEFAULT_MAX_MOVES,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--games" => options.games = args.next()?.parse().ok()?,
+                "--openings" => options.openings = Some(args.next()?),
+                "--pgn" => options.pgn = args.next()?,
+                "--max-moves" => options.max_moves = args.next()?.parse().ok()?,
+                _ if arg.starts_with("--") => {
+                    println!("Invalid argument {arg}");
+                    return None;
+                }
+                _ => engines.push(arg),
+            }
+        }
+        options.engines = engines.try_into().ok()?;
+        Some(options)
+    }
+}
+
+fn read_openings(path: &str) -> Result<Vec<Board>, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|err| format!("Could not read {path}: {err}"))?;
+    let mut openings = Vec::new();
+    for (line_no, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match fen::parse(line) {
+            Some(game) => openings.push(game.board),
+            None => return Err(format!("{path}:{}: invalid FEN", line_no + 1)),
+        }
+    }
+    if openings.is_empty() {
+        return Err(format!("No openings in {path}"));
+    }
+    Ok(openings)
+}
+
+// How a game ended, for the Termination tag
+const NORMAL: &str = "normal";
+const ADJUDICATION: &str = "adjudication";
+const TIME_FORFEIT: &str = "time forfeit";
+
+fn win_for(color: Color) -> GameResult {
+    match color {
+        Color::White => GameResult::WhiteWins,
+        Color::Black => GameResult::BlackWins,
+    }
+}
+
+fn play_game(
+    white: &mut Engine,
+    black: &mut Engine,
+    opening: Board,
+    max_moves: u64,
+) -> (Game, GameResult, &'static str) {
+    let mut game = Game::from_position(opening);
+    let mut clocks = [black.clock.map(|c| c.0), white.clock.map(|c| c.0)];
+    for engine in [&mut *white, &mut *black] {
+        engine.search.tt.clear();
+    }
+    // Who the last engine to move thinks has a forced mate
+    let mut mate_for = None;
+    loop {
+        if game.is_over() {
+            let result = game.result();
+            return (game, result, NORMAL);
+        }
+        if game.log.moves.len() as u64 >= max_moves * 2 {
+            return (game, GameResult::Draw, ADJUDICATION);
+        }
+        let player = game.board.player;
+        let engine = match player {
+            Color::White => &mut *white,
+            Color::Black => &mut *black,
+        };
+        let mut limits = engine.limits;
+        let clock = &mut clocks[player as usize];
+        if let (Some(left), Some((_, inc))) = (*clock, engine.clock) {
+            limits.time_left = Some(left);
+            limits.increment = Some(inc);
+        }
+        engine.search.history = game.history.clone();
+        let start = Instant::now();
+        let result = engine.search.iterate(game.board, &limits, |_, _| ());
+        if let (Some(left), Some((_, inc))) = (*clock, engine.clock) {
+            match left.checked_sub(start.elapsed()) {
+                Some(left) => *clock = Some(left + inc),
+                None => return (game, win_for(player.opponent()), TIME_FORFEIT),
+            }
+        }
+        // A forced mate is as good as played out, but only once both engines agree on it.
+        // Either one could be seeing mates that aren't there, and that's exactly the kind
+        // of bug we'd want a match to catch rather than reward.
+        let winner = result.score.is_mate().then_some(if result.score.0 > 0 {
+            Color::White
+        } else {
+            Color::Black
+        });
+        if let Some(winner) = winner.filter(|winner| mate_for == Some(*winner)) {
+            return (game, win_for(winner), ADJUDICATION);
+        }
+        mate_for = winner;
+        let mv = result
+            .best_move
+            .expect("the game is not over, so there is a move");
+        let alg = game.board.to_algebraic(mv).unwrap();
+        game.make_move(&alg, &mv);
+    }
+}
+
+// Wins, draws and losses for the first engine
+#[derive(Default)]
+struct Tally {
+    wins: usize,
+    draws: usize,
+    losses: usize,
+}
+
+fn elo_from_score(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+impl Tally {
+    fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    // Elo difference and the half-width of its 95% confidence interval. A clean sweep
+    // either way is infinitely many Elo, which is as much as we can say about it.
+    fn elo(&self) -> (f64, f64) {
+        let games = self.games() as f64;
+        let score = self.score();
+        if score == 0.0 || score == 1.0 {
+            return (elo_from_score(score), f64::INFINITY);
+        }
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games;
+        let error = 1.96 * (variance / games).sqrt();
+        let low = elo_from_score((score - error).max(0.0));
+        let high = elo_from_score((score + error).min(1.0));
+        (elo_from_score(score), (high - low) / 2.0)
+    }
+}
+
+impl std::fmt::Display for Tally {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            fmt,
+            "{} - {} - {} [{:.3}] {}",
+            self.wins,
+            self.losses,
+            self.draws,
+            self.score(),
+            self.games()
+        )
+    }
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let [first, second] = &options.engines;
+    let mut engines = [Engine::parse(first)?, Engine::parse(second)?];
+    let openings = match &options.openings {
+        Some(path) => read_openings(path)?,
+        None => vec![Board::initial()],
+    };
+
+    let mut tally = Tally::default();
+    let mut pgn = String::new();
+    for round in 0..options.games {
+        // Each opening is played twice in a row, so both engines get both sides of it
+        let opening = openings[round / 2 % openings.len()];
+        let swapped = round % 2 == 1;
+        let [a, b] = &mut engines;
+        let (white, black) = if swapped { (b, a) } else { (a, b) };
+        let (game, result, termination) = play_game(white, black, opening, options.max_moves);
+
+        let round_no = (round + 1).to_string();
+        let tags = [
+            ("Event", "chess-for-crabs match"),
+            ("Round", round_no.as_str()),
+            ("White", white.name.as_str()),
+            ("Black", black.name.as_str()),
+            ("Termination", termination),
+        ];
+        pgn.push_str(&pgn::write(&game, &tags, result));
+        pgn.push('\n');
+        std::fs::write(&options.pgn, &pgn)
+            .map_err(|err| format!("Could not write {}: {err}", options.pgn))?;
+
+        match (result, swapped) {
+            (GameResult::WhiteWins, false) | (GameResult::BlackWins, true) => tally.wins += 1,
+            (GameResult::WhiteWins, true) | (GameResult::BlackWins, false) => tally.losses += 1,
+            _ => tally.draws += 1,
+        }
+        println!(
+            "Game {round_no} ({} vs {}): {} {{{termination}}}",
+            white.name,
+            black.name,
+            result.as_str()
+        );
+        println!("Score of {first} vs {second}: {tally}");
+    }
+
+    if tally.games() > 0 {
+        let (elo, error) = tally.elo();
+        println!("Elo difference: {elo:.1} +/- {error:.1}");
+    }
+    println!("Games written to {}", options.pgn);
+    Ok(())
+}
+
+fn main() {
+    let Some(options) = Options::parse() else {
+        return print_usage();
+    };
+    if let Err(err) = run(options) {
+        println!("{err}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mates_are_adjudicated_once_both_sides_see_them() {
+        // Mate in two for White
+        let opening = fen::parse("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap().board;
+        let mut white = Engine::parse("material,depth=4").unwrap();
+        // Too shallow to see it coming, so the game gets played out
+        let mut black = Engine::parse("material,depth=1").unwrap();
+        let (game, result, termination) = play_game(&mut white, &mut black, opening, 100);
+        assert!(result == GameResult::WhiteWins);
+        assert_eq!(termination, NORMAL);
+        assert!(game.board.is_checkmate());
+        // Black agrees it's lost after the first move
+        let mut black = Engine::parse("material,depth=4").unwrap();
+        let (adjudicated, result, termination) = play_game(&mut white, &mut black, opening, 100);
+        assert!(result == GameResult::WhiteWins);
+        assert_eq!(termination, ADJUDICATION);
+        assert!(adjudicated.log.moves.len() < game.log.moves.len());
+    }
+
+    #[test]
+    fn elo_difference() {
+        let even = Tally {
+            wins: 10,
+            draws: 20,
+            losses: 10,
+        };
+        let (elo, error) = even.elo();
+        assert_eq!(elo, 0.0);
+        assert!((error - 77.4).abs() < 0.1, "{error}");
+        // 75% is about 190 Elo, and more games mean smaller error bars
+        let (elo, error) = Tally {
+            wins: 50,
+            draws: 50,
+            losses: 0,
+        }
+        .elo();
+        assert!((elo - 190.8).abs() < 0.1, "{elo}");
+        let (_, more_error) = Tally {
+            wins: 5,
+            draws: 5,
+            losses: 0,
+        }
+        .elo();
+        assert!(more_error > error);
+        let (elo, error) = Tally {
+            wins: 3,
+            draws: 0,
+            losses: 0,
+        }
+        .elo();
+        assert!(elo.is_infinite() && error.is_infinite());
+    }
+}
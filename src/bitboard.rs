@@ -207,6 +207,37 @@ impl Bitboard {
         }
     }
 
+    // Fills smear every bit all the way up (towards rank 8) or down the board
+    pub const fn north_fill(self) -> Bitboard {
+        let mut bits = self.0;
+        bits |= bits >> 8;
+        bits |= bits >> 16;
+        bits |= bits >> 32;
+        Bitboard(bits)
+    }
+
+    pub const fn south_fill(self) -> Bitboard {
+        let mut bits = self.0;
+        bits |= bits << 8;
+        bits |= bits << 16;
+        bits |= bits << 32;
+        Bitboard(bits)
+    }
+
+    pub const fn file_fill(self) -> Bitboard {
+        Bitboard(self.north_fill().0 | self.south_fill().0)
+    }
+
+    // One square towards the h file or towards the a file; whatever falls off the edge is
+    // gone rather than wrapping around onto the next rank
+    pub const fn east(self) -> Bitboard {
+        Bitboard((self.0 >> 1) & !0x8080808080808080)
+    }
+
+    pub const fn west(self) -> Bitboard {
+        Bitboard((self.0 << 1) & !0x0101010101010101)
+    }
+
     pub const fn union<const N: usize>(boards: [Bitboard; N]) -> Bitboard {
         let mut pattern: u64 = 0;
         const_for!(i in 0 .. N => {
@@ -227,6 +258,21 @@ impl Bitboard {
 mod tests {
     use super::*;
 
+    #[test]
+    fn bb_fills_and_shifts() {
+        let e4 = Bitboard::at(4, 3);
+        let e_file = Bitboard::union([0, 1, 2, 3, 4, 5, 6, 7].map(|y| Bitboard::at(4, y)));
+        assert!(e4.file_fill() == e_file);
+        let below = Bitboard::union([0, 1, 2].map(|y| Bitboard::at(4, y)));
+        assert!(e4.north_fill() == e_file & !below);
+        assert!((e4.north_fill() | e4.south_fill()) == e_file);
+        assert!(e4.east() == Bitboard::at(5, 3));
+        assert!(e4.west() == Bitboard::at(3, 3));
+        // Nothing wraps around the edges
+        assert!(Bitboard::at(7, 3).east().is_empty());
+        assert!(Bitboard::at(0, 3).west().is_empty());
+    }
+
     #[test]
     fn bb_rank_file() {
         for x in 0..8 {
@@ -2,13 +2,20 @@ use std::fmt::{Debug, Display, Formatter};
 use std::ops::{Index, IndexMut};
 
 use crate::bitboard::{Bitboard, LINE_AT_X, LINE_AT_Y};
-use crate::moves::{AlgebraicMove, Move, SimpleAlgebraicMove, SimpleMove, Square};
+use crate::moves::{
+    AlgebraicMove, CoordinateMove, Move, SimpleAlgebraicMove, SimpleMove, Square,
+};
 use crate::piece::{Color, Piece};
+use crate::zobrist::ZOBRIST;
 use Color::*;
 use Piece::*;
 
 use crate::patterns::*;
 
+// For static exchange evaluation, indexed by Piece. The king is never actually taken, but
+// it's worth more than anything it could take.
+pub const SEE_VALUES: [i64; 6] = [100, 320, 330, 500, 900, 20000];
+
 #[derive(Copy, Clone, PartialEq, Eq)]
 // Alignment does not achieve any performance gains, but it might on a different arch
 #[repr(C, align(64))]
@@ -19,9 +26,13 @@ pub struct Bitboards(pub [Bitboard; 8]);
 pub struct Board {
     pub bitboards: Bitboards,
     pub player: Color,
-    pub half_moves: u8,
+    // Plies since the last capture or pawn move, for the fifty-move rule
+    pub half_moves: u16,
+    // Starts at 1 and goes up after every Black move
+    pub full_moves: u16,
     pub castling_rights: u8,
     pub en_passant: u8,
+    pub hash: u64,
 }
 
 // Mostly used to debug incorrect "illegal move" messages
@@ -85,14 +96,41 @@ const SHORT_CASTLE_INFO: [CastleInfo; 2] = [
     },
 ];
 
+const fn short_castling_right(color: usize) -> u8 {
+    0b10 << (2 * color)
+}
+
+const fn long_castling_right(color: usize) -> u8 {
+    0b01 << (2 * color)
+}
+
+// Castling rights that survive a move from or to each square. Moving the king or a rook, or
+// capturing a rook at home, loses the corresponding rights for good.
+const CASTLING_RIGHTS_MASK: [u8; 64] = {
+    let mut mask = [0b1111; 64];
+    const_for!(color in 0 .. 2 => {
+        const_for!(idx in 0 .. 64 => {
+            if LONG_CASTLE_INFO[color].delete.0 & (1 << idx) != 0 {
+                mask[idx] &= !long_castling_right(color)
+            }
+            if SHORT_CASTLE_INFO[color].delete.0 & (1 << idx) != 0 {
+                mask[idx] &= !short_castling_right(color)
+            }
+        });
+    });
+    mask
+};
+
 impl Board {
     pub fn initial() -> Board {
         let mut b = Board {
             bitboards: Bitboards([Bitboard::empty(); 8]),
             player: White,
             half_moves: 0,
+            full_moves: 1,
             castling_rights: 0b1111,
             en_passant: NO_EN_PASSANT,
+            hash: 0,
         };
         for color in Color::list() {
             for piece in Piece::list() {
@@ -101,17 +139,22 @@ impl Board {
                 b[*piece] = b[*piece] | piece_bitboard;
             }
         }
+        b.hash = b.compute_hash();
         b
     }
 
     pub fn empty() -> Board {
-        Board {
+        let mut b = Board {
             bitboards: Bitboards([Bitboard::empty(); 8]),
             player: White,
             half_moves: 0,
+            full_moves: 1,
             castling_rights: 0,
             en_passant: NO_EN_PASSANT,
-        }
+            hash: 0,
+        };
+        b.hash = b.compute_hash();
+        b
     }
 
     pub fn occupancy(&self) -> Bitboard {
@@ -146,12 +189,34 @@ impl Board {
         } else {
             NO_EN_PASSANT
         };
+        // Captures and pawn moves, including promotions, reset the clock
+        let resets_clock = mv.delete & (self[self.player.opponent()] | self[Pawn]);
+        let half_moves = if resets_clock.is_populated() {
+            0
+        } else {
+            self.half_moves.saturating_add(1)
+        };
+        let mut castling_rights = self.castling_rights;
+        if castling_rights != 0 {
+            for square in (mv.delete | mv.add).occupied() {
+                castling_rights &= CASTLING_RIGHTS_MASK[square.to_index()]
+            }
+        }
+        let hash = self.hash
+            ^ ZOBRIST.placement_delta(&self.bitboards, &bitboards)
+            ^ ZOBRIST.black_to_move
+            ^ ZOBRIST.castling[self.castling_rights as usize]
+            ^ ZOBRIST.castling[castling_rights as usize]
+            ^ ZOBRIST.en_passant[self.en_passant as usize]
+            ^ ZOBRIST.en_passant[en_passant as usize];
         Board {
             bitboards,
             player: self.player.opponent(),
             en_passant,
-            castling_rights: self.castling_rights,
-            half_moves: self.half_moves + 1,
+            castling_rights,
+            half_moves,
+            full_moves: self.next_full_moves(),
+            hash,
         }
     }
 
@@ -161,9 +226,28 @@ impl Board {
         new[King] = (new[King] & !info.delete) | info.add_king;
         new[self.player] = (new[self.player] & !info.delete) | info.add_rook | info.add_king;
         new.player = self.player.opponent();
+        new.en_passant = NO_EN_PASSANT;
+        new.half_moves = self.half_moves.saturating_add(1);
+        new.full_moves = self.next_full_moves();
+        let color = self.player as usize;
+        new.castling_rights =
+            self.castling_rights & !(short_castling_right(color) | long_castling_right(color));
+        new.hash = self.hash
+            ^ ZOBRIST.placement_delta(&self.bitboards, &new.bitboards)
+            ^ ZOBRIST.black_to_move
+            ^ ZOBRIST.castling[self.castling_rights as usize]
+            ^ ZOBRIST.castling[new.castling_rights as usize]
+            ^ ZOBRIST.en_passant[self.en_passant as usize];
         new
     }
 
+    fn next_full_moves(&self) -> u16 {
+        match self.player {
+            White => self.full_moves,
+            Black => self.full_moves.saturating_add(1),
+        }
+    }
+
     pub fn apply(&self, mv: &Move) -> Board {
         match mv {
             Move::Simple(s) => self.apply_simple(s),
@@ -172,6 +256,18 @@ impl Board {
         }
     }
 
+    // Passing, which isn't allowed but is handy for the search to see how good a position
+    // is even if we do nothing. The clock restarts so that no repetition can go through it.
+    pub fn apply_null(&self) -> Board {
+        let mut new = *self;
+        new.player = self.player.opponent();
+        new.en_passant = NO_EN_PASSANT;
+        new.half_moves = 0;
+        new.full_moves = self.next_full_moves();
+        new.hash = self.hash ^ ZOBRIST.black_to_move ^ ZOBRIST.en_passant[self.en_passant as usize];
+        new
+    }
+
     // TODO: pawn moves with blocking can be done with rays and collision testing
     pub fn pawn_move(&self, player: Color, target: Bitboard) -> Bitboard {
         let potential_attackers = REV_PAWN_MOVES[player as usize][target];
@@ -209,31 +305,129 @@ impl Board {
         pattern
     }
 
+    // Pieces of either colour that attack `square`, out of the ones in `occupancy`. Sliders
+    // look through anything that isn't in it, which is how x-rays come in.
+    pub fn attackers_to(&self, square: Bitboard, occupancy: Bitboard) -> Bitboard {
+        let pawns = (REV_PAWN_ATTACKS[White as usize][square] & self[White])
+            | (REV_PAWN_ATTACKS[Black as usize][square] & self[Black]);
+        let diagonal = self[Bishop] | self[Queen];
+        let straight = self[Rook] | self[Queen];
+        let attackers = (pawns & self[Pawn])
+            | (KNIGHT_ATTACKS[square] & self[Knight])
+            | (KING_ATTACKS[square] & self[King])
+            | (Board::bishop_reach_with(occupancy, square) & diagonal)
+            | (Board::rook_reach_with(occupancy, square) & straight);
+        attackers & occupancy
+    }
+
+    // Static exchange evaluation: what the side to move wins, or loses if negative, once
+    // everybody is done taking back on the square `mv` goes to. Both sides always take with
+    // their least valuable piece and can stop whenever it suits them. Pins and promotions
+    // along the way are ignored, so it's not always right, but it's cheap.
+    pub fn see(&self, mv: &Move) -> i64 {
+        let Move::Simple(mv) = mv else {
+            return 0;
+        };
+        let target = mv.add;
+        let from = mv.delete & self[self.player];
+        // Including the pawn taken en passant, which isn't on the target square
+        let taken = mv.delete & self[self.player.opponent()];
+        let mut occupancy = self.occupancy() & !from & !taken;
+        let mut gains = [0; 32];
+        gains[0] = Piece::list()
+            .iter()
+            .find(|piece| (self[**piece] & taken).is_populated())
+            .map_or(0, |piece| SEE_VALUES[*piece as usize]);
+        let moved = *Piece::list()
+            .iter()
+            .find(|piece| (self[**piece] & from).is_populated())
+            .unwrap();
+        if moved != mv.piece {
+            gains[0] += SEE_VALUES[mv.piece as usize] - SEE_VALUES[Pawn as usize];
+        }
+        // Whatever is standing on the target square, waiting to be taken
+        let mut on_target = SEE_VALUES[mv.piece as usize];
+        let mut side = self.player.opponent();
+        let mut depth = 0;
+        while depth + 1 < gains.len() {
+            let attackers = self.attackers_to(target, occupancy);
+            let Some(piece) = Piece::list()
+                .iter()
+                .copied()
+                .find(|piece| (attackers & self[side] & self[*piece]).is_populated())
+            else {
+                break;
+            };
+            // The king can only take if nothing can take it back
+            if piece == King && (attackers & self[side.opponent()]).is_populated() {
+                break;
+            }
+            let attacker = (attackers & self[side] & self[piece]).occupied().next().unwrap();
+            depth += 1;
+            gains[depth] = on_target - gains[depth - 1];
+            occupancy &= !attacker;
+            on_target = SEE_VALUES[piece as usize];
+            side = side.opponent();
+        }
+        // Work back from the end of the sequence: each side takes only if it's worth it
+        while depth > 0 {
+            gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+            depth -= 1;
+        }
+        gains[0]
+    }
+
+    // Canonical SAN: disambiguate by file if that's enough, then by rank, then by both
     pub fn to_algebraic(&self, mv: Move) -> Option<AlgebraicMove> {
-        // TODO: this does not correctly set check/checkmate flags
+        let after = self.apply(&mv);
+        let check = after.in_check(after.player);
+        let checkmate = check && !after.has_legal_moves();
+        let check = check && !checkmate;
         match mv {
-            Move::CastleShort => Some(AlgebraicMove::CastleShort {
-                check: false,
-                checkmate: false,
-            }),
-            Move::CastleLong => Some(AlgebraicMove::CastleLong {
-                check: false,
-                checkmate: false,
-            }),
+            Move::CastleShort => Some(AlgebraicMove::CastleShort { check, checkmate }),
+            Move::CastleLong => Some(AlgebraicMove::CastleLong { check, checkmate }),
             Move::Simple(mv) => {
+                let src = mv.delete & self[self.player];
                 let piece = *Piece::list()
                     .iter()
-                    .find(|piece| (mv.delete & self[self.player] & self[**piece]).is_populated())
-                    .unwrap_or(&mv.piece);
+                    .find(|piece| (src & self[**piece]).is_populated())?;
                 let captures = (mv.delete & self[self.player.opponent()]).is_populated();
+                let (src_x, src_y) = src.coords();
                 let (dst_x, dst_y) = mv.add.coords();
+                let disambiguate = if piece == Pawn {
+                    (captures.then_some(src_x), None)
+                } else {
+                    // Other pieces of the same kind that could have gone there
+                    let mut moves = Vec::with_capacity(32);
+                    self.legal_moves(&mut moves);
+                    let others: Vec<(u8, u8)> = moves
+                        .iter()
+                        .filter_map(|other| match other {
+                            Move::Simple(other) if other.add == mv.add => {
+                                Some(other.delete & self[self.player] & self[piece])
+                            }
+                            _ => None,
+                        })
+                        .filter(|other| other.is_populated() && *other != src)
+                        .map(|other| other.coords())
+                        .collect();
+                    if others.is_empty() {
+                        (None, None)
+                    } else if others.iter().all(|(x, _)| *x != src_x) {
+                        (Some(src_x), None)
+                    } else if others.iter().all(|(_, y)| *y != src_y) {
+                        (None, Some(src_y))
+                    } else {
+                        (Some(src_x), Some(src_y))
+                    }
+                };
                 Some(AlgebraicMove::Simple(SimpleAlgebraicMove {
                     piece,
-                    disambiguate: (None, None),
+                    disambiguate,
                     dst_square: Square::xy(dst_x, dst_y),
                     captures,
-                    check: false,
-                    checkmate: false,
+                    check,
+                    checkmate,
                     promotion: if piece != mv.piece {
                         Some(mv.piece)
                     } else {
@@ -244,6 +438,47 @@ impl Board {
         }
     }
 
+    pub fn to_coordinate(&self, mv: Move) -> CoordinateMove {
+        let square = |bb: Bitboard| {
+            let (x, y) = bb.coords();
+            Square::xy(x, y)
+        };
+        let home = if self.player == White { 0 } else { 7 };
+        match mv {
+            Move::CastleShort => CoordinateMove {
+                src: Square::xy(4, home),
+                dst: Square::xy(6, home),
+                promotion: None,
+            },
+            Move::CastleLong => CoordinateMove {
+                src: Square::xy(4, home),
+                dst: Square::xy(2, home),
+                promotion: None,
+            },
+            Move::Simple(mv) => {
+                let src = mv.delete & self[self.player];
+                let promotion = if (src & self[Pawn]).is_populated() && mv.piece != Pawn {
+                    Some(mv.piece)
+                } else {
+                    None
+                };
+                CoordinateMove {
+                    src: square(src),
+                    dst: square(mv.add),
+                    promotion,
+                }
+            }
+        }
+    }
+
+    // We don't bother working out the move from the squares, we just look for a legal move
+    // with the same coordinates
+    pub fn from_coordinate(&self, mv: &CoordinateMove) -> Option<Move> {
+        let mut moves = Vec::with_capacity(32);
+        self.legal_moves(&mut moves);
+        moves.into_iter().find(|legal| self.to_coordinate(*legal) == *mv)
+    }
+
     pub fn is_pre_legal(&self, mv: &SimpleAlgebraicMove) -> Result<Move, IllegalMove> {
         let piece;
         if let Some(promote_to) = mv.promotion {
@@ -366,8 +601,16 @@ impl Board {
         }
     }
 
+    // Don't trust the rights alone, the king and rook have to actually be there
+    fn castling_pieces_home(&self, info: &CastleInfo) -> bool {
+        let home = info.delete & self[self.player];
+        (home & self[King]).popcnt() == 1 && (home & self[Rook]).popcnt() == 1
+    }
+
     pub fn castle_long(&self) -> Result<Move, IllegalMove> {
-        if !self.long_castling_allowed(self.player) {
+        if !self.long_castling_allowed(self.player)
+            || !self.castling_pieces_home(&LONG_CASTLE_INFO[self.player as usize])
+        {
             return Err(IllegalMove::NoCastlingPermissions);
         }
 
@@ -379,7 +622,9 @@ impl Board {
     }
 
     pub fn castle_short(&self) -> Result<Move, IllegalMove> {
-        if !self.short_castling_allowed(self.player) {
+        if !self.short_castling_allowed(self.player)
+            || !self.castling_pieces_home(&SHORT_CASTLE_INFO[self.player as usize])
+        {
             return Err(IllegalMove::NoCastlingPermissions);
         }
 
@@ -399,16 +644,45 @@ impl Board {
         attackers.is_populated()
     }
 
+    pub fn has_legal_moves(&self) -> bool {
+        let mut moves = Vec::with_capacity(32);
+        self.legal_moves(&mut moves);
+        !moves.is_empty()
+    }
+
+    pub fn is_checkmate(&self) -> bool {
+        self.in_check(self.player) && !self.has_legal_moves()
+    }
+
+    pub fn is_stalemate(&self) -> bool {
+        !self.in_check(self.player) && !self.has_legal_moves()
+    }
+
+    // Neither side can possibly mate: bare kings, a single minor piece, or bishops that all
+    // live on the same colour
+    pub fn is_insufficient_material(&self) -> bool {
+        if (self[Pawn] | self[Rook] | self[Queen]).is_populated() {
+            return false;
+        }
+        let minors = self[Knight] | self[Bishop];
+        if minors.popcnt() <= 1 {
+            return true;
+        }
+        let dark = self[Bishop] & DARK_SQUARES;
+        self[Knight].is_empty() && (dark.is_empty() || dark == self[Bishop])
+    }
+
     pub fn castling_allowed(&self, color: Color) -> bool {
-        0 != self.castling_rights & (0b11 << color as i8)
+        0 != self.castling_rights
+            & (short_castling_right(color as usize) | long_castling_right(color as usize))
     }
 
     pub fn short_castling_allowed(&self, color: Color) -> bool {
-        0 != self.castling_rights & (0b10 << color as i8)
+        0 != self.castling_rights & short_castling_right(color as usize)
     }
 
     pub fn long_castling_allowed(&self, color: Color) -> bool {
-        0 != self.castling_rights & (0b01 << color as i8)
+        0 != self.castling_rights & long_castling_right(color as usize)
     }
 
     pub fn display(&self, w: &mut impl std::io::Write) -> std::io::Result<()> {
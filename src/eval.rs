@@ -1,8 +1,208 @@
+use std::fmt;
+
 use crate::board::Board;
+use crate::king_safety::KingSafety;
+use crate::mobility::Mobility;
+use crate::pawns::Pawns;
 use crate::piece::{Color, Piece};
+use crate::pst::PieceSquareTables;
 
 pub trait Evaluator {
     fn evaluate(&mut self, board: &Board) -> i64;
+
+    // What White and Black get out of it separately, each from their own point of view, for
+    // evaluators that can tell. Only traces use it, so it doesn't need to be quick.
+    fn sides(&mut self, _board: &Board) -> Option<(i64, i64)> {
+        None
+    }
+
+    // The search calls these on its way down and back up every move, for evaluators that
+    // would rather keep running totals than start from scratch in every position
+    fn make_move(&mut self, _before: &Board, _after: &Board) {}
+    fn unmake_move(&mut self) {}
+}
+
+impl<Ev: Evaluator + ?Sized> Evaluator for Box<Ev> {
+    fn evaluate(&mut self, board: &Board) -> i64 {
+        (**self).evaluate(board)
+    }
+
+    fn sides(&mut self, board: &Board) -> Option<(i64, i64)> {
+        (**self).sides(board)
+    }
+
+    fn make_move(&mut self, before: &Board, after: &Board) {
+        (**self).make_move(before, after)
+    }
+
+    fn unmake_move(&mut self) {
+        (**self).unmake_move()
+    }
+}
+
+// A named piece of a bigger evaluation. Weights are percentages, so 100 leaves it as it is.
+pub struct Term {
+    pub name: String,
+    pub weight: i64,
+    pub evaluator: Box<dyn Evaluator + Send>,
+}
+
+impl Term {
+    pub fn new(name: &str, weight: i64) -> Option<Term> {
+        Some(Term {
+            name: name.to_string(),
+            weight,
+            evaluator: term_by_name(name)?,
+        })
+    }
+
+    // Nearly every term is left as it is, and the division shows up in the benchmarks
+    fn weigh(&self, score: i64) -> i64 {
+        if self.weight == 100 {
+            score
+        } else {
+            score * self.weight / 100
+        }
+    }
+}
+
+// Every evaluator that can be picked by name is one of these, even if it only has the one
+// term in it
+pub struct Weighted {
+    pub terms: Vec<Term>,
+}
+
+impl Weighted {
+    pub fn new(terms: Vec<Term>) -> Weighted {
+        Weighted { terms }
+    }
+
+    pub fn trace(&mut self, board: &Board) -> Trace {
+        let lines = self
+            .terms
+            .iter_mut()
+            .map(|term| {
+                let total = term.evaluator.evaluate(board);
+                let sides = term.evaluator.sides(board);
+                TraceLine {
+                    name: term.name.clone(),
+                    weight: term.weight,
+                    sides: sides.map(|(white, black)| (term.weigh(white), term.weigh(black))),
+                    total: term.weigh(total),
+                }
+            })
+            .collect();
+        Trace(lines)
+    }
+}
+
+impl Evaluator for Weighted {
+    fn evaluate(&mut self, board: &Board) -> i64 {
+        self.terms
+            .iter_mut()
+            .map(|term| {
+                let score = term.evaluator.evaluate(board);
+                term.weigh(score)
+            })
+            .sum()
+    }
+
+    fn make_move(&mut self, before: &Board, after: &Board) {
+        for term in self.terms.iter_mut() {
+            term.evaluator.make_move(before, after)
+        }
+    }
+
+    fn unmake_move(&mut self) {
+        for term in self.terms.iter_mut() {
+            term.evaluator.unmake_move()
+        }
+    }
+}
+
+// One row per term, already weighted. Totals are from White's point of view, and add up to
+// what the evaluator says about the position.
+pub struct TraceLine {
+    pub name: String,
+    pub weight: i64,
+    pub sides: Option<(i64, i64)>,
+    pub total: i64,
+}
+
+pub struct Trace(pub Vec<TraceLine>);
+
+impl Trace {
+    pub fn total(&self) -> i64 {
+        self.0.iter().map(|line| line.total).sum()
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>14} | {:>6} | {:>7} | {:>7} | {:>7}",
+            "Term", "Weight", "White", "Black", "Total"
+        )?;
+        writeln!(f, "{:-<15}+{:-<8}+{:-<9}+{:-<9}+{:-<8}", "", "", "", "", "")?;
+        for line in self.0.iter() {
+            let (white, black) = match line.sides {
+                Some((white, black)) => (white.to_string(), black.to_string()),
+                None => ("--".to_string(), "--".to_string()),
+            };
+            writeln!(
+                f,
+                "{:>14} | {:>5}% | {:>7} | {:>7} | {:>7}",
+                line.name, line.weight, white, black, line.total
+            )?;
+        }
+        writeln!(f, "{:-<15}+{:-<8}+{:-<9}+{:-<9}+{:-<8}", "", "", "", "", "")?;
+        write!(f, "{:>14} | {:>6} | {:>7} | {:>7} | {:>7}", "Total", "", "", "", self.total())
+    }
+}
+
+// Everything that can be picked by name from the command line. Names can be joined with a
+// `+` to add terms up, like "pst+pawns", and weighted with a percentage, like "pst+pawns:50".
+pub const EVALUATORS: &[&str] = &[
+    "material",
+    "pst",
+    "pawns",
+    "mobility",
+    "king",
+    "king-attacks",
+    "king-shelter",
+];
+pub const DEFAULT_EVALUATOR: &str = "material";
+
+pub fn by_name(name: &str) -> Option<Weighted> {
+    let mut terms = vec![];
+    for term in name.split('+') {
+        let (name, weight) = match term.split_once(':') {
+            Some((name, weight)) => (name, weight.parse().ok()?),
+            None => (term, 100),
+        };
+        terms.push(Term::new(name, weight)?);
+    }
+    Some(Weighted::new(terms))
+}
+
+fn term_by_name(name: &str) -> Option<Box<dyn Evaluator + Send>> {
+    match name {
+        "material" => Some(Box::new(MaterialCount())),
+        "pst" => Some(Box::new(PieceSquareTables::new())),
+        "pawns" => Some(Box::new(Pawns::new())),
+        "mobility" => Some(Box::new(Mobility())),
+        "king" => Some(Box::new(KingSafety::new())),
+        "king-attacks" => Some(Box::new(KingSafety {
+            attacks: true,
+            shelter: false,
+        })),
+        "king-shelter" => Some(Box::new(KingSafety {
+            attacks: false,
+            shelter: true,
+        })),
+        _ => None,
+    }
 }
 
 pub struct MaterialCount();
@@ -14,7 +214,8 @@ fn piece_value(p: Piece) -> i64 {
         Bishop => 330,
         Rook => 500,
         Queen => 900,
-        King => u32::max_value() as i64,
+        // Both kings are always on the board
+        King => 0,
     }
 }
 
@@ -26,7 +227,7 @@ const PIECE_VALUES: [i64; 8] = [
     330,                     // Bishop
     500,                     // Rook
     900,                     // Queen
-    u32::max_value() as i64, // King
+    0,                       // King
 ];
 
 impl Evaluator for MaterialCount {
@@ -55,4 +256,14 @@ impl Evaluator for MaterialCount {
         }
         */
     }
+
+    fn sides(&mut self, board: &Board) -> Option<(i64, i64)> {
+        let side = |color| {
+            Piece::list()
+                .iter()
+                .map(|piece| piece_value(*piece) * (board[*piece] & board[color]).popcnt())
+                .sum()
+        };
+        Some((side(Color::White), side(Color::Black)))
+    }
 }
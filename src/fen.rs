@@ -1,7 +1,6 @@
 use crate::bitboard::Bitboard;
 use crate::board::Board;
 use crate::game::Game;
-use crate::move_log::MoveLog;
 use crate::patterns::NO_EN_PASSANT;
 use crate::piece::Color::*;
 use crate::piece::Piece::*;
@@ -101,14 +100,11 @@ pub fn parse(s: &str) -> Option<Game> {
     };
     board.castling_rights = read_castling_rights(segments.next()?)?;
     board.en_passant = read_en_passant(segments.next()?)?;
-    board.half_moves = str::parse::<u8>(segments.next()?).ok()?;
-    let fm = str::parse::<i64>(segments.next()?).ok()?;
+    board.half_moves = str::parse::<u16>(segments.next()?).ok()?;
+    board.full_moves = str::parse::<u16>(segments.next()?).ok()?;
+    board.hash = board.compute_hash();
 
-    let log = MoveLog {
-        ply: fm * 2 + if board.player == Black { 1 } else { 0 },
-        moves: Vec::new(),
-    };
-    Some(Game { board, log })
+    Some(Game::from_position(board))
 }
 
 fn write_piece(out: &mut Formatter, color: Color, piece: Piece) -> Result {
@@ -173,6 +169,9 @@ fn serialize_player(out: &mut Formatter, c: Color) -> Result {
 }
 
 fn serialize_castling_rights(out: &mut Formatter, board: &Board) -> Result {
+    if board.castling_rights == 0 {
+        return write!(out, "-");
+    }
     if board.short_castling_allowed(White) {
         write_piece(out, White, King)?;
     }
@@ -213,7 +212,7 @@ impl<'a> Display for FEN<'a> {
         serialize_castling_rights(out, self.0)?;
         write!(out, " ")?;
         serialize_en_passant(out, self.0)?;
-        write!(out, " {} 0", self.0.half_moves) // TODO: serialize half-moves
+        write!(out, " {} {}", self.0.half_moves, self.0.full_moves)
     }
 }
 
@@ -1,34 +1,114 @@
+use std::fmt::{Display, Formatter};
+
 use crate::board::Board;
 use crate::move_log::MoveLog;
 use crate::moves::{AlgebraicMove, Move};
+use crate::piece::Color;
+
+// Counted in plies, like the halfmove clock
+pub const FIFTY_MOVE_RULE: u16 = 100;
+pub const SEVENTY_FIVE_MOVE_RULE: u16 = 150;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Draw {
+    Stalemate,
+    InsufficientMaterial,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+}
+
+impl Display for Draw {
+    fn fmt(&self, fmt: &mut Formatter) -> std::fmt::Result {
+        let msg = match self {
+            Draw::Stalemate => "stalemate",
+            Draw::InsufficientMaterial => "insufficient material",
+            Draw::ThreefoldRepetition => "threefold repetition",
+            Draw::FiftyMoveRule => "fifty-move rule",
+            Draw::SeventyFiveMoveRule => "seventy-five-move rule",
+        };
+        write!(fmt, "{msg}")
+    }
+}
 
 pub struct Game {
+    pub start: Board,
     pub board: Board,
     pub log: MoveLog,
+    // Hashes of every position before the current one, oldest first
+    pub history: Vec<u64>,
 }
 
 impl Game {
     pub fn new() -> Game {
+        Game::from_position(Board::initial())
+    }
+
+    pub fn from_position(board: Board) -> Game {
+        // Plies played before this position, if the game started from the initial one
+        let ply = (board.full_moves.max(1) as i64 - 1) * 2
+            + if board.player == Color::Black { 1 } else { 0 };
         Game {
-            board: Board::initial(),
-            log: MoveLog::new(),
+            start: board,
+            board,
+            log: MoveLog {
+                ply,
+                moves: Vec::new(),
+            },
+            history: Vec::new(),
         }
     }
 
     pub fn make_move(&mut self, alg: &AlgebraicMove, mv: &Move) {
         self.log.append(*alg);
+        self.history.push(self.board.hash);
         self.board = self.board.apply(mv)
     }
 
     pub fn undo_last_move(&mut self) {
-        assert!(self.log.ply > 0);
+        assert!(!self.log.moves.is_empty());
         self.log.ply -= 1;
         self.log.moves.pop();
-        let mut new_board = Board::initial();
+        self.history.pop();
+        let mut new_board = self.start;
         for alg in &self.log.moves {
             let mv = new_board.is_legal(alg).unwrap();
             new_board = new_board.apply(&mv)
         }
         self.board = new_board
     }
+
+    // How many times the current position has occurred, including now. Nothing before the
+    // last capture or pawn move can repeat, so we only look that far back.
+    pub fn repetitions(&self) -> usize {
+        let reversible = (self.board.half_moves as usize).min(self.history.len());
+        1 + self.history[self.history.len() - reversible..]
+            .iter()
+            .filter(|hash| **hash == self.board.hash)
+            .count()
+    }
+
+    // Checkmate aside, the game is over if this returns something
+    pub fn draw(&self) -> Option<Draw> {
+        if self.board.is_stalemate() {
+            Some(Draw::Stalemate)
+        } else if self.board.is_insufficient_material() {
+            Some(Draw::InsufficientMaterial)
+        } else if self.repetitions() >= 3 {
+            Some(Draw::ThreefoldRepetition)
+        } else if self.board.is_checkmate() {
+            // Mate on the move that reaches fifty moves still counts
+            None
+        } else if self.board.half_moves >= SEVENTY_FIVE_MOVE_RULE {
+            Some(Draw::SeventyFiveMoveRule)
+        } else if self.board.half_moves >= FIFTY_MOVE_RULE {
+            Some(Draw::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.board.is_checkmate() || self.draw().is_some()
+    }
 }
@@ -21,7 +21,7 @@ impl Iterator for OccupancyIterator {
 }
 
 impl Bitboard {
-    fn occupied(&self) -> impl Iterator<Item = Bitboard> {
+    pub fn occupied(&self) -> impl Iterator<Item = Bitboard> {
         OccupancyIterator(*self)
     }
 }
@@ -35,10 +35,13 @@ impl Board {
     }
 
     pub fn rook_reach(&self, rook_position: Bitboard) -> Bitboard {
+        Board::rook_reach_with(self.occupancy(), rook_position)
+    }
+
+    // Same as rook_reach, but with any occupancy we like. Useful to look through pieces.
+    pub fn rook_reach_with(occupancy: Bitboard, rook_position: Bitboard) -> Bitboard {
         // At this point I realize my grasp on the correspondence between bits and squares is rather
         // tenuous.
-        let occupancy = self.occupancy();
-
         let (rook_file, rook_rank) = rook_position.coords();
         let rank_shift = 8 * (7 - rook_rank);
         let rank_occupancy: u64 = (occupancy.0 & (0b11111111 << rank_shift)) >> rank_shift;
@@ -53,8 +56,10 @@ impl Board {
     }
 
     pub fn bishop_reach(&self, bishop_position: Bitboard) -> Bitboard {
-        let occupancy = self.occupancy();
+        Board::bishop_reach_with(self.occupancy(), bishop_position)
+    }
 
+    pub fn bishop_reach_with(occupancy: Bitboard, bishop_position: Bitboard) -> Bitboard {
         let mask_nw = NW_DIAGONALS[bishop_position];
         let nw_attacks = Board::hyperbola_quintessence(occupancy, bishop_position, mask_nw);
         let mask_sw = SW_DIAGONALS[bishop_position];
@@ -106,13 +111,21 @@ impl Board {
 
     fn pawn_captures(&self, pos: Bitboard, buffer: &mut Vec<Move>) {
         let cap_tgt = PAWN_ATTACKS[self.player as usize][pos];
+        // Capturing onto the last rank promotes too
+        let pieces: &[Piece] = if (cap_tgt & (LINE_AT_Y[0] | LINE_AT_Y[7])).is_populated() {
+            &[Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        } else {
+            &[Piece::Pawn]
+        };
         // At most 2 captures; one on each side
         for tgt in (cap_tgt & self[self.player.opponent()]).occupied().take(2) {
-            buffer.push(Move::Simple(SimpleMove {
-                delete: pos | tgt,
-                piece: Piece::Pawn,
-                add: tgt,
-            }))
+            for piece in pieces {
+                buffer.push(Move::Simple(SimpleMove {
+                    delete: pos | tgt,
+                    piece: *piece,
+                    add: tgt,
+                }))
+            }
         }
         let ep_info = PAWN_EP_INFO[self.player as usize][self.en_passant as usize];
         // I can't see a way to do e.p. without an extra conditional
@@ -227,4 +240,135 @@ impl Board {
         self.queen_moves(buffer);
         self.king_moves(buffer);
     }
+
+    // Only captures and promotions, for the quiescence search. Pre-legal like the above.
+    pub fn pre_legal_captures(&self, buffer: &mut Vec<Move>) {
+        let last_ranks = LINE_AT_Y[0] | LINE_AT_Y[7];
+        for pawn_pos in (self[self.player] & self[Piece::Pawn]).occupied() {
+            // Pawns about to promote can't double move, so this only adds promotions
+            if (PAWN_SINGLE_MOVES[self.player as usize][pawn_pos] & last_ranks).is_populated() {
+                self.pawn_advances(pawn_pos, buffer);
+            }
+            self.pawn_captures(pawn_pos, buffer);
+        }
+        let targets = self[self.player.opponent()];
+        for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King] {
+            for pos in (self[self.player] & self[piece]).occupied() {
+                let reach = match piece {
+                    Piece::Knight => self.knight_reach(pos),
+                    Piece::Bishop => self.bishop_reach(pos),
+                    Piece::Rook => self.rook_reach(pos),
+                    Piece::Queen => self.queen_reach(pos),
+                    _ => self.king_reach(pos),
+                };
+                for tgt in (reach & targets).occupied() {
+                    buffer.push(Move::Simple(SimpleMove {
+                        delete: pos | tgt,
+                        piece,
+                        add: tgt,
+                    }))
+                }
+            }
+        }
+    }
+
+    // Our pieces that can't leave the line between their king and an enemy slider
+    fn pinned(&self, king: Bitboard) -> Bitboard {
+        let them = self.player.opponent();
+        // Pretend our own pieces aren't there to see who's lined up with the king
+        let snipers = (Board::rook_reach_with(self[them], king)
+            & self[them]
+            & (self[Piece::Rook] | self[Piece::Queen]))
+            | (Board::bishop_reach_with(self[them], king)
+                & self[them]
+                & (self[Piece::Bishop] | self[Piece::Queen]));
+        let mut pinned = Bitboard::empty();
+        for sniper in snipers.occupied() {
+            let blockers = BETWEEN[king][sniper] & self.occupancy();
+            if blockers.popcnt() == 1 {
+                pinned |= blockers & self[self.player]
+            }
+        }
+        pinned
+    }
+
+    fn legal_king_moves(&self, king: Bitboard, in_check: bool, buffer: &mut Vec<Move>) {
+        let them = self.player.opponent();
+        // The king can't hide from a slider by stepping back along its ray, so we look at the
+        // attacks as if it wasn't there
+        let mut without_king = *self;
+        without_king[self.player] &= !king;
+        without_king[Piece::King] &= !king;
+        for tgt in (self.king_reach(king) & !self[self.player]).occupied() {
+            if without_king.capture_to(them, tgt).is_empty() {
+                buffer.push(Move::Simple(SimpleMove {
+                    delete: king | tgt,
+                    piece: Piece::King,
+                    add: tgt,
+                }))
+            }
+        }
+        if in_check {
+            return;
+        }
+        // castle_short and castle_long already check the square the king crosses, but not the
+        // one it lands on. Castling is rare enough that we can afford to just try it.
+        for mv in [self.castle_short(), self.castle_long()].into_iter().flatten() {
+            if !self.apply(&mv).in_check(self.player) {
+                buffer.push(mv)
+            }
+        }
+    }
+
+    pub fn legal_moves(&self, buffer: &mut Vec<Move>) {
+        let king = self[self.player] & self[Piece::King];
+        if king.is_empty() {
+            // Not a real game, but there's no way of leaving a missing king in check
+            return self.pre_legal_moves(buffer);
+        }
+        let checkers = self.capture_to(self.player.opponent(), king);
+        if checkers.popcnt() > 1 {
+            // Double check, only the king can do something about it
+            return self.legal_king_moves(king, true, buffer);
+        }
+        // Where a piece needs to go to deal with a check: capture the checker or get in its way
+        let check_mask = if checkers.is_empty() {
+            Bitboard(!0)
+        } else {
+            checkers | BETWEEN[king][checkers]
+        };
+        let pinned = self.pinned(king);
+
+        let start = buffer.len();
+        self.pawn_moves(buffer);
+        self.knight_moves(buffer);
+        self.bishop_moves(buffer);
+        self.rook_moves(buffer);
+        self.queen_moves(buffer);
+        let mut kept = start;
+        for i in start..buffer.len() {
+            let Move::Simple(mv) = buffer[i] else {
+                unreachable!("Only the king castles")
+            };
+            let src = mv.delete & self[self.player];
+            // Every other capture empties the square it moves to, and pushes only empty one
+            let en_passant = mv.piece == Piece::Pawn
+                && mv.delete.popcnt() == 2
+                && (mv.delete & mv.add).is_empty();
+            let legal = if en_passant {
+                // En passant removes two pieces from the same rank, which can uncover a check
+                // that no pin detects. It's rare, so do it the slow way.
+                !self.apply_simple(&mv).in_check(self.player)
+            } else {
+                (mv.add & check_mask).is_populated()
+                    && ((src & pinned).is_empty() || (mv.add & LINE[king][src]).is_populated())
+            };
+            if legal {
+                buffer.swap(kept, i);
+                kept += 1
+            }
+        }
+        buffer.truncate(kept);
+        self.legal_king_moves(king, checkers.is_populated(), buffer);
+    }
 }
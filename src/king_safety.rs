@@ -0,0 +1,111 @@
+use crate::bitboard::Bitboard;
+use crate::board::Board;
+use crate::eval::Evaluator;
+use crate::mobility::reach;
+use crate::patterns::FILES;
+use crate::pawns::forward;
+use crate::piece::{Color, Piece};
+
+// Per square of the king zone a piece attacks, indexed by Piece
+const ATTACK_WEIGHTS: [i64; 6] = [0, 20, 20, 40, 80, 0];
+// One piece poking at the king is nothing to worry about, several at once are. Percentage of
+// the attack weight that counts, by number of attackers.
+const ATTACKER_SCALE: [i64; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+// Per file next to the king without a pawn right in front of it
+const MISSING_SHIELD_PAWN: i64 = -15;
+// Per file next to the king without pawns of our own, or without any pawns at all
+const HALF_OPEN_FILE: i64 = -15;
+const OPEN_FILE: i64 = -25;
+
+// The squares around the king, and the king's own
+pub fn king_zone(board: &Board, color: Color) -> Bitboard {
+    let king = board[Piece::King] & board[color];
+    king | board.king_reach(king)
+}
+
+// How hard the other side is going after `color`'s king, as a penalty
+pub fn king_attacks(board: &Board, color: Color) -> i64 {
+    let zone = king_zone(board, color);
+    let enemy = color.opponent();
+    let (mut attackers, mut weight) = (0_usize, 0);
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        for square in (board[piece] & board[enemy]).occupied() {
+            let hits = (reach(board, piece, enemy, square) & zone).popcnt();
+            if hits > 0 {
+                attackers += 1;
+                weight += ATTACK_WEIGHTS[piece as usize] * hits;
+            }
+        }
+    }
+    -weight * ATTACKER_SCALE[attackers.min(7)] / 100
+}
+
+// Pawns in front of the king and files around it. Only worth worrying about while there's
+// a queen around to take advantage.
+pub fn king_shelter(board: &Board, color: Color) -> i64 {
+    if (board[Piece::Queen] & board[color.opponent()]).is_empty() {
+        return 0;
+    }
+    let king = board[Piece::King] & board[color];
+    let own_pawns = board[Piece::Pawn] & board[color];
+    let one_up = forward(king | king.east() | king.west(), color);
+    let shield = one_up | forward(one_up, color);
+    let mut score = 0;
+    for square in (king | king.east() | king.west()).occupied() {
+        let file = FILES[square];
+        if (own_pawns & shield & file).is_empty() {
+            score += MISSING_SHIELD_PAWN;
+        }
+        if (board[Piece::Pawn] & file).is_empty() {
+            score += OPEN_FILE;
+        } else if (own_pawns & file).is_empty() {
+            score += HALF_OPEN_FILE;
+        }
+    }
+    score
+}
+
+// Both halves can be turned off to see what each of them is worth
+pub struct KingSafety {
+    pub attacks: bool,
+    pub shelter: bool,
+}
+
+impl KingSafety {
+    pub fn new() -> KingSafety {
+        KingSafety {
+            attacks: true,
+            shelter: true,
+        }
+    }
+
+    fn score(&self, board: &Board, color: Color) -> i64 {
+        let mut score = 0;
+        if self.attacks {
+            score += king_attacks(board, color);
+        }
+        if self.shelter {
+            score += king_shelter(board, color);
+        }
+        score
+    }
+}
+
+impl Default for KingSafety {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Evaluator for KingSafety {
+    fn evaluate(&mut self, board: &Board) -> i64 {
+        self.score(board, Color::White) - self.score(board, Color::Black)
+    }
+
+    fn sides(&mut self, board: &Board) -> Option<(i64, i64)> {
+        Some((
+            self.score(board, Color::White),
+            self.score(board, Color::Black),
+        ))
+    }
+}
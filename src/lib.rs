@@ -10,9 +10,19 @@ pub mod eval;
 pub mod fen;
 pub mod game;
 pub mod gen;
+pub mod king_safety;
+pub mod mobility;
 pub mod move_log;
 pub mod moves;
+pub mod ordering;
 pub mod patterns;
+pub mod pawns;
+pub mod perft;
+pub mod pgn;
 pub mod piece;
+pub mod pst;
 pub mod search;
+pub mod tt;
 pub mod types;
+pub mod uci;
+pub mod zobrist;
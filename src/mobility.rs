@@ -0,0 +1,47 @@
+use crate::bitboard::Bitboard;
+use crate::board::Board;
+use crate::eval::Evaluator;
+use crate::pawns::pawn_attacks;
+use crate::piece::{Color, Piece};
+
+// Per square a piece can go to, indexed by Piece. Pawns and kings don't get any.
+const MOBILITY_WEIGHTS: [i64; 6] = [0, 4, 5, 2, 1, 0];
+
+// Squares a piece on `square` attacks, whatever is standing on them
+pub fn reach(board: &Board, piece: Piece, color: Color, square: Bitboard) -> Bitboard {
+    match piece {
+        Piece::Knight => board.knight_reach(square),
+        Piece::Bishop => board.bishop_reach(square),
+        Piece::Rook => board.rook_reach(square),
+        Piece::Queen => board.queen_reach(square),
+        Piece::King => board.king_reach(square),
+        Piece::Pawn => pawn_attacks(square, color),
+    }
+}
+
+// Squares each piece could move to, leaving out its own pieces and anywhere an enemy pawn
+// would just take it
+pub fn mobility(board: &Board, color: Color) -> i64 {
+    let enemy = color.opponent();
+    let safe = !board[color] & !pawn_attacks(board[Piece::Pawn] & board[enemy], enemy);
+    let mut score = 0;
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        for square in (board[piece] & board[color]).occupied() {
+            let moves = reach(board, piece, color, square) & safe;
+            score += MOBILITY_WEIGHTS[piece as usize] * moves.popcnt();
+        }
+    }
+    score
+}
+
+pub struct Mobility();
+
+impl Evaluator for Mobility {
+    fn evaluate(&mut self, board: &Board) -> i64 {
+        mobility(board, Color::White) - mobility(board, Color::Black)
+    }
+
+    fn sides(&mut self, board: &Board) -> Option<(i64, i64)> {
+        Some((mobility(board, Color::White), mobility(board, Color::Black)))
+    }
+}
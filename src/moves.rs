@@ -7,21 +7,21 @@ use crate::piece::Piece::*;
 
 
 // Beware: in a promotion, `piece` is the type of the promoted piece
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct SimpleMove {
     pub delete: Bitboard,
     pub piece: Piece,
     pub add: Bitboard,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Move {
     Simple(SimpleMove),
     CastleLong,
     CastleShort,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Square {
     pub x: u8,
     pub y: u8,
@@ -31,6 +31,13 @@ impl Square {
     pub fn xy(x: u8, y: u8) -> Square {
         Square { x, y }
     }
+
+    pub fn parse(s: &str) -> Option<Square> {
+        match s.as_bytes() {
+            [x @ b'a'..=b'h', y @ b'1'..=b'8'] => Some(Square::xy(x - b'a', y - b'1')),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for Square {
@@ -39,6 +46,42 @@ impl std::fmt::Display for Square {
     }
 }
 
+// Long algebraic notation, as spoken by UCI: e2e4, e7e8q, and the king's move for castling (e1g1)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CoordinateMove {
+    pub src: Square,
+    pub dst: Square,
+    pub promotion: Option<Piece>,
+}
+
+impl CoordinateMove {
+    pub fn parse(s: &str) -> Option<CoordinateMove> {
+        if !s.is_ascii() || !(4..=5).contains(&s.len()) {
+            return None;
+        }
+        let promotion = match s[4..].chars().next() {
+            None => None,
+            Some(chr @ ('n' | 'b' | 'r' | 'q')) => Piece::from_algebraic(chr.to_ascii_uppercase()),
+            Some(_) => return None,
+        };
+        Some(CoordinateMove {
+            src: Square::parse(&s[0..2])?,
+            dst: Square::parse(&s[2..4])?,
+            promotion,
+        })
+    }
+}
+
+impl Display for CoordinateMove {
+    fn fmt(&self, fmt: &mut Formatter) -> std::fmt::Result {
+        write!(fmt, "{}{}", self.src, self.dst)?;
+        if let Some(piece) = self.promotion {
+            write!(fmt, "{}", piece.algebraic().to_ascii_lowercase())?
+        }
+        Ok(())
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct SimpleAlgebraicMove {
     pub piece: Piece,
@@ -263,11 +306,12 @@ impl Display for AlgebraicMove {
             Pawn => (),
             piece => write!(fmt, "{}", piece.algebraic())?,
         };
+        let file = |f: u8| (f + b'a') as char;
         match mv.disambiguate {
             (None, None) => (),
-            (Some(f), Some(r)) => write!(fmt, "{}{}", f, r)?,
-            (Some(f), None) => write!(fmt, "{}", f)?,
-            (None, Some(r)) => write!(fmt, "{}", r)?,
+            (Some(f), Some(r)) => write!(fmt, "{}{}", file(f), r + 1)?,
+            (Some(f), None) => write!(fmt, "{}", file(f))?,
+            (None, Some(r)) => write!(fmt, "{}", r + 1)?,
         };
         if mv.captures {
             write!(fmt, "x")?
@@ -277,7 +321,7 @@ impl Display for AlgebraicMove {
             write!(fmt, "={}", promote_to.algebraic())?
         }
         if mv.checkmate {
-            write!(fmt, "*")?
+            write!(fmt, "#")?
         } else if mv.check {
             write!(fmt, "+")?
         }
@@ -0,0 +1,221 @@
+use crate::board::Board;
+use crate::moves::Move;
+use crate::piece::{Color, Piece};
+
+// Good enough to tell which captures can't possibly lose material, indexed by Piece. A king
+// can only take things nobody defends, so it never loses anything by capturing.
+const TRADE_VALUES: [i64; 6] = [1, 3, 3, 5, 9, 0];
+
+// Moves are tried in bands, best first:
+//   1. the hash move, or the best move of the previous iteration at the root
+//   2. captures that don't give away material once the exchange is over, most valuable
+//      victim first and least valuable attacker after that, and promotions to a queen
+//   3. killer moves for this ply
+//   4. captures that do lose material
+//   5. everything else, by history
+const HASH_MOVE: i64 = 1 << 40;
+const GOOD_CAPTURE: i64 = 1 << 32;
+const KILLER: i64 = 1 << 28;
+const BAD_CAPTURE: i64 = 1 << 24;
+// History scores are halved when any of them gets this high, so they stay below the
+// captures, and old cutoffs matter less than recent ones
+const MAX_HISTORY: i64 = 1 << 20;
+
+pub const KILLERS_PER_PLY: usize = 2;
+
+// What the search has learned about quiet moves so far
+pub struct MoveOrdering {
+    // Quiet moves that caused a cutoff at each ply, most recent first
+    killers: Vec<[Option<Move>; KILLERS_PER_PLY]>,
+    // How often moving a piece to a square has caused a cutoff, indexed by
+    // [color][piece][square], weighted by depth
+    history: Box<[[[i64; 64]; 6]; 2]>,
+}
+
+impl MoveOrdering {
+    pub fn new() -> MoveOrdering {
+        MoveOrdering {
+            killers: Vec::new(),
+            history: Box::new([[[0; 64]; 6]; 2]),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.killers.clear();
+        *self.history = [[[0; 64]; 6]; 2];
+    }
+
+    pub fn killers(&self, ply: usize) -> &[Option<Move>] {
+        self.killers.get(ply).map_or(&[], |killers| &killers[..])
+    }
+
+    pub fn history(&self, color: Color, mv: &Move) -> i64 {
+        match mv {
+            Move::Simple(mv) => self.history[color as usize][mv.piece as usize][mv.add.to_index()],
+            _ => 0,
+        }
+    }
+
+    // Call with the move that caused a beta cutoff; captures and promotions are left alone,
+    // they get ordered well enough without help
+    pub fn cutoff(&mut self, pos: &Board, mv: Move, depth: u64, ply: usize) {
+        if is_tactical(pos, &mv) {
+            return;
+        }
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; KILLERS_PER_PLY]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv) {
+            killers.rotate_right(1);
+            killers[0] = Some(mv);
+        }
+        if let Move::Simple(simple) = mv {
+            let entry = &mut self.history[pos.player as usize][simple.piece as usize]
+                [simple.add.to_index()];
+            *entry += (depth * depth) as i64;
+            if *entry >= MAX_HISTORY {
+                for score in self.history.iter_mut().flatten().flatten() {
+                    *score /= 2
+                }
+            }
+        }
+    }
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The piece a move takes, if any. En passant takes a pawn that isn't on the target square,
+// but it's still on a square the move empties.
+pub fn victim(pos: &Board, mv: &Move) -> Option<Piece> {
+    let Move::Simple(mv) = mv else {
+        return None;
+    };
+    let taken = mv.delete & pos[pos.player.opponent()];
+    if taken.is_empty() {
+        return None;
+    }
+    Piece::list()
+        .iter()
+        .copied()
+        .find(|piece| (pos[*piece] & taken).is_populated())
+}
+
+pub fn attacker(pos: &Board, mv: &Move) -> Piece {
+    match mv {
+        Move::Simple(mv) => {
+            let from = mv.delete & pos[pos.player];
+            Piece::list()
+                .iter()
+                .copied()
+                .find(|piece| (pos[*piece] & from).is_populated())
+                .unwrap()
+        }
+        _ => Piece::King,
+    }
+}
+
+// The piece a pawn turns into, if the move is a promotion
+fn promotion(pos: &Board, mv: &Move) -> Option<Piece> {
+    match mv {
+        Move::Simple(simple) => Some(simple.piece).filter(|piece| {
+            *piece != Piece::Pawn
+                && (simple.delete & pos[Piece::Pawn] & pos[pos.player]).is_populated()
+        }),
+        _ => None,
+    }
+}
+
+fn queen_promotion(pos: &Board, mv: &Move) -> bool {
+    promotion(pos, mv) == Some(Piece::Queen)
+}
+
+// Underpromotions only go first when they capture something, but they're never quiet: the
+// search doesn't prune or reduce them any more than queen promotions
+pub fn is_tactical(pos: &Board, mv: &Move) -> bool {
+    victim(pos, mv).is_some() || promotion(pos, mv).is_some()
+}
+
+// Taking something at least as valuable as the attacker never loses anything, so the
+// exchange only needs working out when it might
+pub fn losing_capture(pos: &Board, mv: &Move, victim: Piece, attacker: Piece) -> bool {
+    TRADE_VALUES[victim as usize] < TRADE_VALUES[attacker as usize] && pos.see(mv) < 0
+}
+
+// Most valuable victim, least valuable attacker
+fn mvv_lva(victim: Piece, attacker: Piece) -> i64 {
+    TRADE_VALUES[victim as usize] * 16 - TRADE_VALUES[attacker as usize]
+}
+
+// Hands out moves best first. Sorting all of them up front would be a waste, since a
+// cutoff usually comes after the first few.
+pub struct MovePicker {
+    moves: Vec<(Move, i64)>,
+    next: usize,
+}
+
+impl MovePicker {
+    pub fn new(
+        pos: &Board,
+        moves: &[Move],
+        hash_move: Option<Move>,
+        ordering: &MoveOrdering,
+        ply: usize,
+    ) -> MovePicker {
+        let killers = ordering.killers(ply);
+        let moves = moves
+            .iter()
+            .map(|mv| {
+                let score = if Some(*mv) == hash_move {
+                    HASH_MOVE
+                } else if let Some(victim) = victim(pos, mv) {
+                    let attacker = attacker(pos, mv);
+                    let band = if losing_capture(pos, mv, victim, attacker) {
+                        BAD_CAPTURE
+                    } else {
+                        GOOD_CAPTURE
+                    };
+                    band + mvv_lva(victim, attacker)
+                } else if queen_promotion(pos, mv) {
+                    GOOD_CAPTURE + mvv_lva(Piece::Queen, Piece::Pawn)
+                } else if let Some(i) = killers.iter().position(|killer| *killer == Some(*mv)) {
+                    KILLER - i as i64
+                } else {
+                    ordering.history(pos.player, mv)
+                };
+                (*mv, score)
+            })
+            .collect();
+        MovePicker { moves, next: 0 }
+    }
+
+    // Just the hash move in front, and the rest as they came out of the generator
+    pub fn unordered(moves: &[Move], hash_move: Option<Move>) -> MovePicker {
+        let moves = moves
+            .iter()
+            .map(|mv| (*mv, if Some(*mv) == hash_move { HASH_MOVE } else { 0 }))
+            .collect();
+        MovePicker { moves, next: 0 }
+    }
+}
+
+impl Iterator for MovePicker {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        let remaining = &mut self.moves[self.next..];
+        // The first one wins ties, so equal moves keep the generator's order
+        let (best, _) = remaining
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, (_, score))| *score)?;
+        remaining[..=best].rotate_right(1);
+        self.next += 1;
+        Some(remaining[0].0)
+    }
+}
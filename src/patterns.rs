@@ -341,3 +341,66 @@ pub const SHORT_CASTLE_PATH: [Bitboard; 2] = [
 ];
 pub const LONG_CASTLE_MID_SQUARE: [Bitboard; 2] = [Bitboard::at(3, 7), Bitboard::at(3, 0)];
 pub const SHORT_CASTLE_MID_SQUARE: [Bitboard; 2] = [Bitboard::at(5, 7), Bitboard::at(5, 0)];
+
+// Indexed by two squares. BETWEEN holds the squares strictly between them, and LINE the whole
+// line (edge to edge) going through both. Both are empty if the squares aren't on the same rank,
+// file or diagonal.
+pub const BETWEEN: SquareIndex<SquareIndex<Bitboard>> = precompute_lines(false);
+pub const LINE: SquareIndex<SquareIndex<Bitboard>> = precompute_lines(true);
+
+const fn precompute_lines(full_line: bool) -> SquareIndex<SquareIndex<Bitboard>> {
+    let mut lines = SquareIndex([SquareIndex::new(); 64]);
+    const_for!(x0 in 0 .. 8 => {
+        const_for!(y0 in 0 .. 8 => {
+            const_for!(x1 in 0 .. 8 => {
+                const_for!(y1 in 0 .. 8 => {
+                    let (dx, dy): (i32, i32) = (x1 - x0, y1 - y0);
+                    let aligned = (dx != 0 || dy != 0)
+                        && (dx == 0 || dy == 0 || dx == dy || dx == -dy);
+                    if aligned {
+                        let (sx, sy) = (dx.signum(), dy.signum());
+                        let mut pat = 0;
+                        let (mut x, mut y) = if full_line {
+                            // Walk back to the edge of the board first
+                            let (mut x, mut y) = (x0, y0);
+                            while Bitboard::at_checked(x - sx, y - sy).is_some() {
+                                x -= sx;
+                                y -= sy;
+                            }
+                            (x, y)
+                        } else {
+                            (x0 + sx, y0 + sy)
+                        };
+                        while let Some(bb) = Bitboard::at_checked(x, y) {
+                            if !full_line && x == x1 && y == y1 {
+                                break;
+                            }
+                            pat |= bb.0;
+                            x += sx;
+                            y += sy;
+                        }
+                        let a = Bitboard::at(x0 as u8, y0 as u8);
+                        let b = Bitboard::at(x1 as u8, y1 as u8);
+                        *lines.get_mut(a).get_mut(b) = Bitboard(pat);
+                    }
+                });
+            });
+        });
+    });
+    lines
+}
+
+const fn precompute_dark_squares() -> Bitboard {
+    let mut dark = 0;
+    const_for!(x in 0 .. 8 => {
+        const_for!(y in 0 .. 8 => {
+            // a1 is dark
+            if (x + y) % 2 == 0 {
+                dark |= Bitboard::at(x, y).0;
+            }
+        })
+    });
+    Bitboard(dark)
+}
+
+pub const DARK_SQUARES: Bitboard = precompute_dark_squares();
@@ -0,0 +1,212 @@
+use crate::bitboard::Bitboard;
+use crate::board::Board;
+use crate::eval::Evaluator;
+use crate::patterns::FILES;
+use crate::piece::{Color, Piece};
+use crate::zobrist::ZOBRIST;
+
+const DOUBLED: i64 = -15;
+const ISOLATED: i64 = -15;
+const BACKWARD: i64 = -10;
+const CONNECTED: i64 = 8;
+// Indexed by rank, counting from the pawn's own side of the board
+const PASSED: [i64; 8] = [0, 5, 10, 20, 35, 60, 100, 0];
+
+pub const DEFAULT_PAWN_TABLE_ENTRIES: usize = 1 << 14;
+
+// Everything here is written from White's point of view, with these to turn it around
+pub fn forward(pawns: Bitboard, color: Color) -> Bitboard {
+    match color {
+        Color::White => Bitboard(pawns.0 >> 8),
+        Color::Black => Bitboard(pawns.0 << 8),
+    }
+}
+
+fn one_back(pawns: Bitboard, color: Color) -> Bitboard {
+    forward(pawns, color.opponent())
+}
+
+fn forward_fill(pawns: Bitboard, color: Color) -> Bitboard {
+    match color {
+        Color::White => pawns.north_fill(),
+        Color::Black => pawns.south_fill(),
+    }
+}
+
+// Every square in front of the pawns, not counting the ones they're on
+fn front_span(pawns: Bitboard, color: Color) -> Bitboard {
+    forward(forward_fill(pawns, color), color)
+}
+
+pub fn pawn_attacks(pawns: Bitboard, color: Color) -> Bitboard {
+    let ahead = forward(pawns, color);
+    ahead.east() | ahead.west()
+}
+
+// The pawns of one side that have each feature
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PawnStructure {
+    // Behind another pawn of the same colour, so the front one doesn't count
+    pub doubled: Bitboard,
+    // No friendly pawns on either neighbouring file
+    pub isolated: Bitboard,
+    // Can't be defended by other pawns if it moves up, and can't move up safely either
+    pub backward: Bitboard,
+    // No enemy pawns in front, on the same or neighbouring files
+    pub passed: Bitboard,
+    // Defended by another pawn, or side by side with one
+    pub connected: Bitboard,
+}
+
+impl PawnStructure {
+    pub fn new(own: Bitboard, enemy: Bitboard, color: Color) -> PawnStructure {
+        let doubled = own & front_span(own, color.opponent());
+
+        let mut isolated = Bitboard::empty();
+        for pawn in own.occupied() {
+            let neighbours = FILES[pawn].east() | FILES[pawn].west();
+            if (own & neighbours).is_empty() {
+                isolated |= pawn
+            }
+        }
+
+        // Squares our pawns could defend, now or after moving up
+        let ahead = front_span(own, color);
+        let defensible = ahead.east() | ahead.west();
+        let unsafe_stops =
+            forward(own, color) & !defensible & pawn_attacks(enemy, color.opponent());
+        let backward = own & one_back(unsafe_stops, color);
+
+        let blocked = front_span(enemy, color.opponent());
+        let passed = own & !(blocked | blocked.east() | blocked.west()) & !doubled;
+
+        let connected = own & (pawn_attacks(own, color) | own.east() | own.west());
+
+        PawnStructure {
+            doubled,
+            isolated,
+            backward,
+            passed,
+            connected,
+        }
+    }
+
+    // From the point of view of the side these pawns belong to
+    pub fn score(&self, color: Color) -> i64 {
+        let mut score = DOUBLED * self.doubled.popcnt()
+            + ISOLATED * self.isolated.popcnt()
+            + BACKWARD * self.backward.popcnt()
+            + CONNECTED * self.connected.popcnt();
+        for pawn in self.passed.occupied() {
+            let rank = match color {
+                Color::White => pawn.rank(),
+                Color::Black => 7 - pawn.rank(),
+            };
+            score += PASSED[rank as usize];
+        }
+        score
+    }
+}
+
+// Pawn structure only, from White's point of view
+pub fn pawn_score(board: &Board) -> i64 {
+    let white = board[Piece::Pawn] & board[Color::White];
+    let black = board[Piece::Pawn] & board[Color::Black];
+    PawnStructure::new(white, black, Color::White).score(Color::White)
+        - PawnStructure::new(black, white, Color::Black).score(Color::Black)
+}
+
+// Like the board's hash, but only for the pawns
+pub fn pawn_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for color in Color::list() {
+        for pawn in (board[Piece::Pawn] & board[*color]).occupied() {
+            key ^= ZOBRIST.piece(*color, Piece::Pawn, pawn)
+        }
+    }
+    key
+}
+
+#[derive(Copy, Clone)]
+struct PawnEntry {
+    key: u64,
+    score: i64,
+}
+
+// Pawns hardly ever move, so most positions in a search share their pawn structure with
+// plenty of others. Same idea as the transposition table, always replacing.
+pub struct PawnHashTable {
+    entries: Vec<Option<PawnEntry>>,
+    mask: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl PawnHashTable {
+    pub fn new(entries: usize) -> Self {
+        let len = if entries == 0 {
+            0
+        } else {
+            1 << entries.ilog2()
+        };
+        PawnHashTable {
+            entries: vec![None; len],
+            mask: (len as u64).wrapping_sub(1),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn score(&mut self, board: &Board) -> i64 {
+        if self.entries.is_empty() {
+            return pawn_score(board);
+        }
+        let key = pawn_key(board);
+        let slot = &mut self.entries[(key & self.mask) as usize];
+        match slot {
+            Some(entry) if entry.key == key => {
+                self.hits += 1;
+                entry.score
+            }
+            _ => {
+                self.misses += 1;
+                let score = pawn_score(board);
+                *slot = Some(PawnEntry { key, score });
+                score
+            }
+        }
+    }
+}
+
+pub struct Pawns {
+    pub table: PawnHashTable,
+}
+
+impl Pawns {
+    pub fn new() -> Pawns {
+        Pawns {
+            table: PawnHashTable::new(DEFAULT_PAWN_TABLE_ENTRIES),
+        }
+    }
+}
+
+impl Default for Pawns {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Evaluator for Pawns {
+    fn evaluate(&mut self, board: &Board) -> i64 {
+        self.table.score(board)
+    }
+
+    fn sides(&mut self, board: &Board) -> Option<(i64, i64)> {
+        let white = board[Piece::Pawn] & board[Color::White];
+        let black = board[Piece::Pawn] & board[Color::Black];
+        Some((
+            PawnStructure::new(white, black, Color::White).score(Color::White),
+            PawnStructure::new(black, white, Color::Black).score(Color::Black),
+        ))
+    }
+}
@@ -0,0 +1,32 @@
+use crate::board::Board;
+use crate::moves::Move;
+
+impl Board {
+    // Number of leaf nodes of the legal move tree `depth` plies deep
+    pub fn perft(&self, depth: u64) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut moves = Vec::with_capacity(32);
+        self.legal_moves(&mut moves);
+        // No need to play out the last ply, the moves are the leaves
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves.iter().map(|mv| self.apply(mv).perft(depth - 1)).sum()
+    }
+
+    // Perft split by root move, to find which subtree disagrees with a reference engine.
+    // At depth 0 no moves get played, so there's nothing to split.
+    pub fn divide(&self, depth: u64) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        let mut moves = Vec::with_capacity(32);
+        self.legal_moves(&mut moves);
+        moves
+            .into_iter()
+            .map(|mv| (mv, self.apply(&mv).perft(depth - 1)))
+            .collect()
+    }
+}
@@ -0,0 +1,525 @@
+use std::fmt::{Display, Formatter};
+
+use crate::board::{Board, IllegalMove};
+use crate::fen;
+use crate::game::Game;
+use crate::moves::{AlgebraicMove, Move};
+use crate::piece::Color;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    // Still going, abandoned, or we just don't know
+    Unknown,
+}
+
+impl GameResult {
+    pub fn parse(s: &str) -> Option<GameResult> {
+        match s {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        }
+    }
+}
+
+pub struct PgnMove {
+    pub alg: AlgebraicMove,
+    pub mv: Move,
+    // Numeric annotation glyphs, with !, ?, !? and friends turned into their $n equivalents
+    pub nags: Vec<u8>,
+    // Comments that follow the move
+    pub comments: Vec<String>,
+    // Alternatives to this move, each starting from the position before it
+    pub variations: Vec<Line>,
+}
+
+#[derive(Default)]
+pub struct Line {
+    // Comments that come before the first move
+    pub comments: Vec<String>,
+    pub moves: Vec<PgnMove>,
+}
+
+pub struct PgnGame {
+    // In the order they appear in the file
+    pub tags: Vec<(String, String)>,
+    // From the FEN tag, if there is one
+    pub start: Board,
+    pub mainline: Line,
+    pub result: GameResult,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Replays the main line, dropping all the annotations
+    pub fn to_game(&self) -> Game {
+        let mut game = Game::from_position(self.start);
+        for mv in &self.mainline.moves {
+            game.make_move(&mv.alg, &mv.mv)
+        }
+        game
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PgnErrorKind {
+    UnexpectedCharacter(char),
+    UnterminatedComment,
+    MalformedTag,
+    InvalidFen(String),
+    InvalidMove(String),
+    IllegalMove(String, IllegalMove),
+    // A variation or NAG before there's any move for it to refer to
+    NothingToAnnotate,
+    UnterminatedVariation,
+    UnbalancedParenthesis,
+}
+
+impl Display for PgnErrorKind {
+    fn fmt(&self, fmt: &mut Formatter) -> std::fmt::Result {
+        use PgnErrorKind::*;
+        match self {
+            UnexpectedCharacter(chr) => write!(fmt, "unexpected character {chr:?}"),
+            UnterminatedComment => write!(fmt, "unterminated comment"),
+            MalformedTag => write!(fmt, "malformed tag pair"),
+            InvalidFen(fen) => write!(fmt, "invalid FEN {fen:?}"),
+            InvalidMove(mv) => write!(fmt, "cannot parse move {mv:?}"),
+            IllegalMove(mv, illegal) => write!(fmt, "illegal move {mv}: {}", illegal.as_str()),
+            NothingToAnnotate => write!(fmt, "annotation or variation before any move"),
+            UnterminatedVariation => write!(fmt, "unterminated variation"),
+            UnbalancedParenthesis => write!(fmt, "closing parenthesis outside of a variation"),
+        }
+    }
+}
+
+// Game numbers start at 1, as do lines and columns
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PgnError {
+    pub game: usize,
+    pub line: usize,
+    pub column: usize,
+    pub kind: PgnErrorKind,
+}
+
+impl Display for PgnError {
+    fn fmt(&self, fmt: &mut Formatter) -> std::fmt::Result {
+        write!(
+            fmt,
+            "game {}, line {}, column {}: {}",
+            self.game, self.line, self.column, self.kind
+        )
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Location {
+    line: usize,
+    column: usize,
+}
+
+enum Token<'a> {
+    Tag(String, String),
+    Comment(String),
+    OpenVariation,
+    CloseVariation,
+    Nag(u8),
+    Result(GameResult),
+    Move(&'a str),
+}
+
+// Suffix annotations and the NAGs they stand for
+const SUFFIXES: [(&str, u8); 6] = [
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+    ("!", 1),
+    ("?", 2),
+];
+
+struct Lexer<'a> {
+    input: &'a str,
+    pos: usize,
+    loc: Location,
+    game: usize,
+    peeked: Option<(Location, Token<'a>)>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(input: &'a str) -> Self {
+        Lexer {
+            input,
+            pos: 0,
+            loc: Location { line: 1, column: 1 },
+            game: 1,
+            peeked: None,
+        }
+    }
+
+    fn error(&self, loc: Location, kind: PgnErrorKind) -> PgnError {
+        PgnError {
+            game: self.game,
+            line: loc.line,
+            column: loc.column,
+            kind,
+        }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let chr = self.peek_char()?;
+        self.pos += chr.len_utf8();
+        if chr == '\n' {
+            self.loc.line += 1;
+            self.loc.column = 1;
+        } else {
+            self.loc.column += 1;
+        }
+        Some(chr)
+    }
+
+    fn bump_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek_char().is_some_and(&pred) {
+            self.bump();
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek_char() {
+                Some(chr) if chr.is_whitespace() => {
+                    self.bump();
+                }
+                // Rest-of-line comments, and escaped lines for other programs to deal with
+                Some(';') => {
+                    self.bump_while(|chr| chr != '\n');
+                }
+                Some('%') if self.loc.column == 1 => {
+                    self.bump_while(|chr| chr != '\n');
+                }
+                _ => return,
+            }
+        }
+    }
+
+    // [Name "value"]
+    fn tag(&mut self, start: Location) -> Result<Token<'a>, PgnError> {
+        let malformed = |lexer: &Self| lexer.error(start, PgnErrorKind::MalformedTag);
+        self.skip_whitespace();
+        let name = self.bump_while(|chr| chr.is_alphanumeric() || chr == '_');
+        self.skip_whitespace();
+        if name.is_empty() || self.bump() != Some('"') {
+            return Err(malformed(self));
+        }
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => break,
+                Some('\\') => value.extend(self.bump()),
+                Some('\n') | None => return Err(malformed(self)),
+                Some(chr) => value.push(chr),
+            }
+        }
+        self.skip_whitespace();
+        if self.bump() != Some(']') {
+            return Err(malformed(self));
+        }
+        Ok(Token::Tag(name.to_string(), value))
+    }
+
+    fn next(&mut self) -> Result<Option<(Location, Token<'a>)>, PgnError> {
+        if let Some(peeked) = self.peeked.take() {
+            return Ok(Some(peeked));
+        }
+        loop {
+            self.skip_whitespace();
+            let start = self.loc;
+            let Some(chr) = self.bump() else {
+                return Ok(None);
+            };
+            let token = match chr {
+                '[' => self.tag(start)?,
+                '{' => {
+                    let comment = self.bump_while(|chr| chr != '}');
+                    if self.bump().is_none() {
+                        return Err(self.error(start, PgnErrorKind::UnterminatedComment));
+                    }
+                    Token::Comment(comment.trim().to_string())
+                }
+                '(' => Token::OpenVariation,
+                ')' => Token::CloseVariation,
+                '*' => Token::Result(GameResult::Unknown),
+                '$' => match self.bump_while(|chr| chr.is_ascii_digit()).parse() {
+                    Ok(nag) => Token::Nag(nag),
+                    Err(_) => return Err(self.error(start, PgnErrorKind::UnexpectedCharacter('$'))),
+                },
+                // Black's move after a comment or variation is written 12... Nf6
+                '.' => continue,
+                chr if chr.is_alphanumeric() => {
+                    let symbol_start = self.pos - chr.len_utf8();
+                    self.bump_while(|chr| chr.is_alphanumeric() || "+#=-/!?_:".contains(chr));
+                    let symbol = &self.input[symbol_start..self.pos];
+                    if let Some(result) = GameResult::parse(symbol) {
+                        Token::Result(result)
+                    } else if symbol.chars().all(|chr| chr.is_ascii_digit()) {
+                        // Move numbers only help humans find their way around
+                        self.bump_while(|chr| chr == '.');
+                        continue;
+                    } else {
+                        Token::Move(symbol)
+                    }
+                }
+                chr => return Err(self.error(start, PgnErrorKind::UnexpectedCharacter(chr))),
+            };
+            return Ok(Some((start, token)));
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&(Location, Token<'a>)>, PgnError> {
+        if self.peeked.is_none() {
+            self.peeked = self.next()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+}
+
+fn parse_move(
+    lexer: &Lexer,
+    loc: Location,
+    board: &Board,
+    symbol: &str,
+) -> Result<PgnMove, PgnError> {
+    let mut san = symbol;
+    let mut nags = Vec::new();
+    if let Some((suffix, nag)) = SUFFIXES.iter().find(|(suffix, _)| symbol.ends_with(suffix)) {
+        san = &symbol[..symbol.len() - suffix.len()];
+        nags.push(*nag);
+    }
+    // Plenty of files castle with zeroes instead of letters
+    let letters;
+    if san.starts_with("0-0") {
+        letters = san.replace('0', "O");
+        san = &letters;
+    }
+    let alg = AlgebraicMove::parse(san)
+        .ok_or_else(|| lexer.error(loc, PgnErrorKind::InvalidMove(symbol.to_string())))?;
+    let mv = board
+        .is_legal(&alg)
+        .map_err(|err| lexer.error(loc, PgnErrorKind::IllegalMove(symbol.to_string(), err)))?;
+    Ok(PgnMove {
+        alg,
+        mv,
+        nags,
+        comments: Vec::new(),
+        variations: Vec::new(),
+    })
+}
+
+// Reads moves from `board` on until the end of the variation, or of the game if `nested` is
+// false. The result is only there for the main line.
+fn parse_line(
+    lexer: &mut Lexer,
+    board: Board,
+    nested: bool,
+) -> Result<(Line, GameResult), PgnError> {
+    let mut line = Line::default();
+    let mut board = board;
+    // The position before the last move, where its variations start from
+    let mut previous = board;
+    loop {
+        // A tag means the next game has started, even if this one had no result
+        if !nested && matches!(lexer.peek()?, Some((_, Token::Tag(..)))) {
+            return Ok((line, GameResult::Unknown));
+        }
+        let Some((loc, token)) = lexer.next()? else {
+            if nested {
+                return Err(lexer.error(lexer.loc, PgnErrorKind::UnterminatedVariation));
+            }
+            return Ok((line, GameResult::Unknown));
+        };
+        match token {
+            Token::Tag(..) => return Err(lexer.error(loc, PgnErrorKind::UnterminatedVariation)),
+            Token::Comment(comment) => match line.moves.last_mut() {
+                Some(mv) => mv.comments.push(comment),
+                None => line.comments.push(comment),
+            },
+            Token::Nag(nag) => match line.moves.last_mut() {
+                Some(mv) => mv.nags.push(nag),
+                None => return Err(lexer.error(loc, PgnErrorKind::NothingToAnnotate)),
+            },
+            Token::Move(symbol) => {
+                let mv = parse_move(lexer, loc, &board, symbol)?;
+                previous = board;
+                board = board.apply(&mv.mv);
+                line.moves.push(mv);
+            }
+            Token::OpenVariation => {
+                if line.moves.is_empty() {
+                    return Err(lexer.error(loc, PgnErrorKind::NothingToAnnotate));
+                }
+                let (variation, _) = parse_line(lexer, previous, true)?;
+                line.moves.last_mut().unwrap().variations.push(variation);
+            }
+            Token::CloseVariation if nested => return Ok((line, GameResult::Unknown)),
+            Token::CloseVariation => {
+                return Err(lexer.error(loc, PgnErrorKind::UnbalancedParenthesis))
+            }
+            Token::Result(_) if nested => {
+                return Err(lexer.error(loc, PgnErrorKind::UnterminatedVariation))
+            }
+            Token::Result(result) => return Ok((line, result)),
+        }
+    }
+}
+
+fn parse_game(lexer: &mut Lexer) -> Result<Option<PgnGame>, PgnError> {
+    let mut tags = Vec::new();
+    let mut start = Board::initial();
+    while let Some((loc, Token::Tag(..))) = lexer.peek()? {
+        let loc = *loc;
+        let Some((_, Token::Tag(name, value))) = lexer.next()? else {
+            unreachable!()
+        };
+        if name == "FEN" {
+            start = match fen::parse(&value) {
+                Some(game) => game.board,
+                None => return Err(lexer.error(loc, PgnErrorKind::InvalidFen(value))),
+            }
+        }
+        tags.push((name, value));
+    }
+    if tags.is_empty() && lexer.peek()?.is_none() {
+        return Ok(None);
+    }
+    let (mainline, result) = parse_line(lexer, start, false)?;
+    Ok(Some(PgnGame {
+        tags,
+        start,
+        mainline,
+        result,
+    }))
+}
+
+// Reads every game in a PGN database
+pub fn parse(input: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut lexer = Lexer::new(input);
+    let mut games = Vec::new();
+    while let Some(game) = parse_game(&mut lexer)? {
+        games.push(game);
+        lexer.game += 1;
+    }
+    Ok(games)
+}
+
+// Writing
+
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+const MAX_LINE_LENGTH: usize = 80;
+
+impl Game {
+    // What the rules say about the current position
+    pub fn result(&self) -> GameResult {
+        if self.board.is_checkmate() {
+            match self.board.player {
+                Color::White => GameResult::BlackWins,
+                Color::Black => GameResult::WhiteWins,
+            }
+        } else if self.draw().is_some() {
+            GameResult::Draw
+        } else {
+            GameResult::Unknown
+        }
+    }
+}
+
+fn write_tag(out: &mut String, name: &str, value: &str) {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    out.push_str(&format!("[{name} \"{value}\"]\n"));
+}
+
+// Seven Tag Roster first, filled in from `tags` or left unknown, then whatever else is in
+// `tags`. The result in the movetext always comes from `result`.
+pub fn write(game: &Game, tags: &[(&str, &str)], result: GameResult) -> String {
+    let mut out = String::new();
+    let find = |name: &str| tags.iter().find(|(tag, _)| *tag == name).map(|(_, v)| *v);
+    for (name, default) in SEVEN_TAG_ROSTER {
+        let value = match name {
+            "Result" => result.as_str(),
+            _ => find(name).unwrap_or(default),
+        };
+        write_tag(&mut out, name, value);
+    }
+    if game.start != Board::initial() {
+        write_tag(&mut out, "SetUp", "1");
+        write_tag(&mut out, "FEN", &game.start.fen().to_string());
+    }
+    for (name, value) in tags {
+        if !SEVEN_TAG_ROSTER.iter().any(|(tag, _)| tag == name) && !["SetUp", "FEN"].contains(name)
+        {
+            write_tag(&mut out, name, value);
+        }
+    }
+    out.push('\n');
+
+    let mut tokens = Vec::new();
+    let mut board = game.start;
+    for (i, alg) in game.log.moves.iter().enumerate() {
+        let mv = board.is_legal(alg).unwrap();
+        match board.player {
+            Color::White => tokens.push(format!("{}.", board.full_moves)),
+            Color::Black if i == 0 => tokens.push(format!("{}...", board.full_moves)),
+            Color::Black => (),
+        }
+        tokens.push(board.to_algebraic(mv).unwrap().to_string());
+        board = board.apply(&mv);
+    }
+    tokens.push(result.as_str().to_string());
+
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+            out.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            out.push(' ');
+            line_length += 1;
+        }
+        line_length += token.len();
+        out.push_str(&token);
+    }
+    out.push('\n');
+    out
+}
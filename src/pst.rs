@@ -0,0 +1,302 @@
+use crate::bitboard::Bitboard;
+use crate::board::Board;
+use crate::eval::Evaluator;
+use crate::piece::{Color, Piece};
+
+// Piece-square tables, with separate middlegame and endgame values that get blended
+// according to how much material is left. The numbers are PeSTO's (Ronald Friederich),
+// which were tuned for exactly this kind of evaluator.
+
+// Middlegame and endgame value of each piece, indexed by Piece
+const MG_MATERIAL: [i32; 6] = [82, 337, 365, 477, 1025, 0];
+const EG_MATERIAL: [i32; 6] = [94, 281, 297, 512, 936, 0];
+
+// How much each piece counts towards the game phase. With everything on the board the
+// phase is MAX_PHASE, pure middlegame; with only pawns and kings left it is 0, pure endgame.
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+pub const MAX_PHASE: i32 = 24;
+
+// All tables from White's point of view, the way you'd look at a diagram: a8 first, h1 last
+#[rustfmt::skip]
+const MG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     98, 134,  61,  95,  68, 126,  34, -11,
+     -6,   7,  26,  31,  65,  56,  25, -20,
+    -14,  13,   6,  21,  23,  12,  17, -23,
+    -27,  -2,  -5,  12,  17,   6,  10, -25,
+    -26,  -4,  -4, -10,   3,   3,  33, -12,
+    -35,  -1, -20, -23, -15,  24,  38, -22,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const EG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+     94, 100,  85,  67,  56,  53,  82,  84,
+     32,  24,  13,   5,  -2,   4,  17,  17,
+     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+     13,   8,   8,  10,  13,   0,   2,  -7,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const MG_KNIGHT: [i32; 64] = [
+   -167, -89, -34, -49,  61, -97, -15,-107,
+    -73, -41,  72,  36,  23,  62,   7, -17,
+    -47,  60,  37,  65,  84, 129,  73,  44,
+     -9,  17,  19,  53,  37,  69,  18,  22,
+    -13,   4,  16,  13,  28,  19,  21,  -8,
+    -23,  -9,  12,  10,  19,  17,  25, -16,
+    -29, -53, -12,  -3,  -1,  18, -14, -19,
+   -105, -21, -58, -33, -17, -28, -19, -23,
+];
+#[rustfmt::skip]
+const EG_KNIGHT: [i32; 64] = [
+    -58, -38, -13, -28, -31, -27, -63, -99,
+    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+    -24, -20,  10,   9,  -1,  -9, -19, -41,
+    -17,   3,  22,  22,  22,  11,   8, -18,
+    -18,  -6,  16,  25,  16,  17,   4, -18,
+    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+    -42, -20, -10,  -5,  -2, -20, -23, -44,
+    -29, -51, -23, -15, -22, -18, -50, -64,
+];
+#[rustfmt::skip]
+const MG_BISHOP: [i32; 64] = [
+    -29,   4, -82, -37, -25, -42,   7,  -8,
+    -26,  16, -18, -13,  30,  59,  18, -47,
+    -16,  37,  43,  40,  35,  50,  37,  -2,
+     -4,   5,  19,  50,  37,  37,   7,  -2,
+     -6,  13,  13,  26,  34,  12,  10,   4,
+      0,  15,  15,  15,  14,  27,  18,  10,
+      4,  15,  16,   0,   7,  21,  33,   1,
+    -33,  -3, -14, -21, -13, -12, -39, -21,
+];
+#[rustfmt::skip]
+const EG_BISHOP: [i32; 64] = [
+    -14, -21, -11,  -8,  -7,  -9, -17, -24,
+     -8,  -4,   7, -12,  -3, -13,  -4, -14,
+      2,  -8,   0,  -1,  -2,   6,   0,   4,
+     -3,   9,  12,   9,  14,  10,   3,   2,
+     -6,   3,  13,  19,   7,  10,  -3,  -9,
+    -12,  -3,   8,  10,  13,   3,  -7, -15,
+    -14, -18,  -7,  -1,   4,  -9, -15, -27,
+    -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+];
+#[rustfmt::skip]
+const MG_ROOK: [i32; 64] = [
+     32,  42,  32,  51,  63,   9,  31,  43,
+     27,  32,  58,  62,  80,  67,  26,  44,
+     -5,  19,  26,  36,  17,  45,  61,  16,
+    -24, -11,   7,  26,  24,  35,  -8, -20,
+    -36, -26, -12,  -1,   9,  -7,   6, -23,
+    -45, -25, -16, -17,   3,   0,  -5, -33,
+    -44, -16, -20,  -9,  -1,  11,  -6, -71,
+    -19, -13,   1,  17,  16,   7, -37, -26,
+];
+#[rustfmt::skip]
+const EG_ROOK: [i32; 64] = [
+     13,  10,  18,  15,  12,  12,   8,   5,
+     11,  13,  13,  11,  -3,   3,   8,   3,
+      7,   7,   7,   5,   4,  -3,  -5,  -3,
+      4,   3,  13,   1,   2,   1,  -1,   2,
+      3,   5,   8,   4,  -5,  -6,  -8, -11,
+     -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+     -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+     -9,   2,   3,  -1,  -5, -13,   4, -20,
+];
+#[rustfmt::skip]
+const MG_QUEEN: [i32; 64] = [
+    -28,   0,  29,  12,  59,  44,  43,  45,
+    -24, -39,  -5,   1, -16,  57,  28,  54,
+    -13, -17,   7,   8,  29,  56,  47,  57,
+    -27, -27, -16, -16,  -1,  17,  -2,   1,
+     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+    -14,   2, -11,  -2,  -5,   2,  14,   5,
+    -35,  -8,  11,   2,   8,  15,  -3,   1,
+     -1, -18,  -9,  10, -15, -25, -31, -50,
+];
+#[rustfmt::skip]
+const EG_QUEEN: [i32; 64] = [
+     -9,  22,  22,  27,  27,  19,  10,  20,
+    -17,  20,  32,  41,  58,  25,  30,   0,
+    -20,   6,   9,  49,  47,  35,  19,   9,
+      3,  22,  24,  45,  57,  40,  57,  36,
+    -18,  28,  19,  47,  31,  34,  39,  23,
+    -16, -27,  15,   6,   9,  17,  10,   5,
+    -22, -23, -30, -16, -16, -23, -36, -32,
+    -33, -28, -22, -43,  -5, -32, -20, -41,
+];
+#[rustfmt::skip]
+const MG_KING: [i32; 64] = [
+    -65,  23,  16, -15, -56, -34,   2,  13,
+     29,  -1, -20,  -7,  -8,  -4, -38, -29,
+     -9,  24,   2, -16, -20,   6,  22, -22,
+    -17, -20, -12, -27, -30, -25, -14, -36,
+    -49,  -1, -27, -39, -46, -44, -33, -51,
+    -14, -14, -22, -46, -44, -30, -15, -27,
+      1,   7,  -8, -64, -43, -16,   9,   8,
+    -15,  36,  12, -54,   8, -28,  24,  14,
+];
+#[rustfmt::skip]
+const EG_KING: [i32; 64] = [
+    -74, -35, -18, -18, -11,  15,   4, -17,
+    -12,  17,  14,  17,  17,  38,  23,  11,
+     10,  17,  23,  15,  20,  45,  44,  13,
+     -8,  22,  24,  27,  26,  33,  26,   3,
+    -18,  -4,  21,  24,  27,  23,   9, -11,
+    -19,  -3,  11,  21,  23,  16,   7,  -9,
+    -27, -11,   4,  13,  14,   4,  -5, -17,
+    -53, -34, -21, -11, -28, -14, -24, -43,
+];
+
+// Turns a diagram-ordered table into one indexed like the bitboards, material included and
+// signed so that everything can just be added up from White's point of view
+const fn bitboard_order(table: &[i32; 64], material: i32, color: Color) -> [i32; 64] {
+    let mut result = [0; 64];
+    let mut idx = 0;
+    while idx < 64 {
+        // Bit 63 is a1 and bit 0 is h8, which is the diagram order mirrored horizontally
+        let (rank, file) = (7 - idx / 8, 7 - idx % 8);
+        result[idx] = match color {
+            Color::White => material + table[(7 - rank) * 8 + file],
+            Color::Black => -(material + table[rank * 8 + file]),
+        };
+        idx += 1;
+    }
+    result
+}
+
+const fn both_colors(mg: &[i32; 64], eg: &[i32; 64], piece: Piece) -> [[[i32; 64]; 2]; 2] {
+    let (mg_material, eg_material) = (MG_MATERIAL[piece as usize], EG_MATERIAL[piece as usize]);
+    [
+        [
+            bitboard_order(mg, mg_material, Color::Black),
+            bitboard_order(mg, mg_material, Color::White),
+        ],
+        [
+            bitboard_order(eg, eg_material, Color::Black),
+            bitboard_order(eg, eg_material, Color::White),
+        ],
+    ]
+}
+
+// Indexed by piece, then middlegame/endgame, then colour, then square
+const TABLES: [[[[i32; 64]; 2]; 2]; 6] = [
+    both_colors(&MG_PAWN, &EG_PAWN, Piece::Pawn),
+    both_colors(&MG_KNIGHT, &EG_KNIGHT, Piece::Knight),
+    both_colors(&MG_BISHOP, &EG_BISHOP, Piece::Bishop),
+    both_colors(&MG_ROOK, &EG_ROOK, Piece::Rook),
+    both_colors(&MG_QUEEN, &EG_QUEEN, Piece::Queen),
+    both_colors(&MG_KING, &EG_KING, Piece::King),
+];
+
+// Running totals for a position, from White's point of view
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PstScore {
+    pub mg: i32,
+    pub eg: i32,
+    // Not clamped to MAX_PHASE, promotions can push it over
+    pub phase: i32,
+}
+
+impl PstScore {
+    pub fn new(board: &Board) -> PstScore {
+        let mut score = PstScore {
+            mg: 0,
+            eg: 0,
+            phase: 0,
+        };
+        for piece in Piece::list() {
+            for color in Color::list() {
+                score.add(*piece, *color, board[*piece] & board[*color], 1);
+            }
+        }
+        score
+    }
+
+    // Just one side's pieces, with the phase still counted over the whole board
+    pub fn side(board: &Board, color: Color) -> PstScore {
+        let mut score = PstScore {
+            mg: 0,
+            eg: 0,
+            phase: 0,
+        };
+        for piece in Piece::list() {
+            score.add(*piece, color, board[*piece] & board[color], 1);
+        }
+        score.phase = PstScore::new(board).phase;
+        score
+    }
+
+    fn add(&mut self, piece: Piece, color: Color, squares: Bitboard, sign: i32) {
+        let tables = &TABLES[piece as usize];
+        for square in squares.occupied() {
+            let idx = square.to_index();
+            self.mg += sign * tables[0][color as usize][idx];
+            self.eg += sign * tables[1][color as usize][idx];
+            self.phase += sign * PHASE_WEIGHTS[piece as usize];
+        }
+    }
+
+    // Only looks at the squares that changed between `before` and `after`, which is a
+    // handful at most whatever the move was (castling, en passant, promotions...)
+    pub fn update(mut self, before: &Board, after: &Board) -> PstScore {
+        for piece in Piece::list() {
+            for color in Color::list() {
+                let was = before[*piece] & before[*color];
+                let is = after[*piece] & after[*color];
+                self.add(*piece, *color, was & !is, -1);
+                self.add(*piece, *color, is & !was, 1);
+            }
+        }
+        self
+    }
+
+    pub fn taper(&self) -> i64 {
+        let phase = self.phase.min(MAX_PHASE);
+        ((self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE) as i64
+    }
+}
+
+// Keeps a stack of running totals along the line being searched, so that most positions
+// don't need to be added up from scratch. Every entry remembers which position it's for, so
+// anything that isn't on top of the stack just gets evaluated the slow way.
+#[derive(Default)]
+pub struct PieceSquareTables {
+    stack: Vec<(u64, PstScore)>,
+}
+
+impl PieceSquareTables {
+    pub fn new() -> PieceSquareTables {
+        PieceSquareTables::default()
+    }
+
+    fn score(&self, board: &Board) -> PstScore {
+        match self.stack.last() {
+            Some((hash, score)) if *hash == board.hash => *score,
+            _ => PstScore::new(board),
+        }
+    }
+}
+
+impl Evaluator for PieceSquareTables {
+    fn evaluate(&mut self, board: &Board) -> i64 {
+        self.score(board).taper()
+    }
+
+    fn sides(&mut self, board: &Board) -> Option<(i64, i64)> {
+        let white = PstScore::side(board, Color::White).taper();
+        let black = PstScore::side(board, Color::Black).taper();
+        Some((white, -black))
+    }
+
+    fn make_move(&mut self, before: &Board, after: &Board) {
+        let score = self.score(before).update(before, after);
+        self.stack.push((after.hash, score))
+    }
+
+    fn unmake_move(&mut self) {
+        self.stack.pop();
+    }
+}
@@ -1,6 +1,13 @@
-use std::cmp::{max, min};
+use std::cmp::max;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::{board::Board, eval::Evaluator, moves::Move, piece::Color};
+use crate::board::SEE_VALUES;
+use crate::game::FIFTY_MOVE_RULE;
+use crate::ordering::{attacker, is_tactical, losing_capture, victim, MoveOrdering, MovePicker};
+use crate::tt::{Bound, Entry, TranspositionTable, DEFAULT_TT_SIZE_MB};
+use crate::{board::Board, eval::Evaluator, moves::Move, piece::Color, piece::Piece};
 
 const MAX_MOVES: usize = 28 * (1 + 8) // Max possible queen moves
     + 14 * 2 // Max possible rook moves
@@ -9,88 +16,812 @@ const MAX_MOVES: usize = 28 * (1 + 8) // Max possible queen moves
     + (9 + 2) // Max possible king moves
 ;
 
+// Deeper than this and we will never finish an iteration anyway
+pub const MAX_DEPTH: u64 = 64;
+// Captures that can't bring the score back up to alpha even with this much to spare aren't
+// worth looking at in the quiescence search
+const DELTA_MARGIN: i64 = 200;
+// How much shallower the search after a null move is, on top of the move itself
+const NULL_MOVE_REDUCTION: u64 = 2;
+const NULL_MOVE_MIN_DEPTH: u64 = 3;
+// Moves after this many get reduced, by one ply, or two much further down the list
+const LMR_MIN_MOVES: usize = 3;
+const LMR_MIN_DEPTH: u64 = 3;
+const LMR_LATE_MOVES: usize = 8;
+// History score good enough to be let off one ply of the reduction
+const LMR_GOOD_HISTORY: i64 = 1024;
+// Per ply of depth left, how far above beta or below alpha the static evaluation needs to
+// be for a position or a quiet move not to be worth searching
+const REVERSE_FUTILITY_MARGIN: i64 = 90;
+const REVERSE_FUTILITY_MAX_DEPTH: u64 = 5;
+const FUTILITY_MARGINS: [i64; 3] = [0, 200, 400];
+// How many nodes we search between looks at the clock
+const CLOCK_CHECK_INTERVAL: i64 = 1024;
+
+// Mate scores count down from MATE by the number of plies to the mate, so faster mates
+// score higher and slower losses score less badly. Anything within MAX_MATE_PLY of MATE
+// is a mate, everything else is centipawns.
+pub const MATE: i64 = 1_000_000;
+const MAX_MATE_PLY: i64 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Score(pub i64);
+
+impl Score {
+    pub fn is_mate(self) -> bool {
+        self.0.abs() >= MATE - MAX_MATE_PLY
+    }
+
+    // Full moves until mate, negative if we are the ones getting mated
+    pub fn mate_in(self) -> Option<i64> {
+        if !self.is_mate() {
+            return None;
+        }
+        let plies = MATE - self.0.abs();
+        Some(self.0.signum() * (plies + 1) / 2)
+    }
+}
+
+impl std::ops::Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score(-self.0)
+    }
+}
+
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.mate_in() {
+            // The sign has to come from the score, being mated right now is mate in 0 too
+            Some(moves) if self.0 < 0 => write!(f, "-#{}", -moves),
+            Some(moves) => write!(f, "#{moves}"),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+// Score for the side to move having been mated `ply` plies from the root
+fn mated(ply: usize) -> i64 {
+    -MATE + ply as i64
+}
+
+// Wider than any real score, and safe to negate
+const INFINITY: i64 = i64::MAX;
+
+// Mate scores are relative to the root, but the TT shares them between nodes at different
+// plies, so store them relative to the node instead
+fn score_to_tt(score: i64, ply: usize) -> i64 {
+    if Score(score).is_mate() {
+        score + score.signum() * ply as i64
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i64, ply: usize) -> i64 {
+    if Score(score).is_mate() {
+        score - score.signum() * ply as i64
+    } else {
+        score
+    }
+}
+
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    // From White's point of view, even though the search works from the side to move's
+    pub score: Score,
+    pub pv: Vec<Move>,
+    pub depth: u64,
+    pub nodes: i64,
+}
+
+// Everything unset means "think until told to stop"
+#[derive(Clone, Copy, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u64>,
+    pub nodes: Option<i64>,
+    pub movetime: Option<Duration>,
+    // Clock for the side to move
+    pub time_left: Option<Duration>,
+    pub increment: Option<Duration>,
+    pub moves_to_go: Option<u64>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u64) -> Self {
+        SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn movetime(movetime: Duration) -> Self {
+        SearchLimits {
+            movetime: Some(movetime),
+            ..Default::default()
+        }
+    }
+
+    // Returns (soft, hard) limits: we don't start a new iteration past the soft one, and we
+    // abort the current one past the hard one
+    fn time_budget(&self) -> Option<(Duration, Duration)> {
+        if let Some(movetime) = self.movetime {
+            return Some((movetime, movetime));
+        }
+        let left = self.time_left?;
+        let inc = self.increment.unwrap_or(Duration::ZERO);
+        let moves_to_go = self.moves_to_go.unwrap_or(30).max(1) as u32;
+        // Never plan on using more than half of what's left
+        let hard = (left / moves_to_go + inc / 2).min(left / 2);
+        // The next iteration will take several times longer than this one, so
+        // there's no point in starting it once we're halfway through
+        Some((hard / 2, hard))
+    }
+}
+
+// Parts of the search that can be switched off, mostly to measure what they're worth
+#[derive(Clone, Copy)]
+pub struct SearchOptions {
+    // Captures, killers and history first. Without it only the hash move goes first.
+    pub move_ordering: bool,
+    // Keep going with captures past the horizon, rather than evaluate mid-exchange
+    pub quiescence: bool,
+    // Let the other side move twice in a row, and if we're still doing fine, don't bother
+    pub null_move: bool,
+    // Search moves late in the list less deeply, unless they turn out better than expected
+    pub late_move_reductions: bool,
+    // Near the leaves, give up on positions that are too good to be allowed...
+    pub reverse_futility: bool,
+    // ...and on quiet moves in positions too bad to be saved by one
+    pub futility: bool,
+}
+
+// Names for the options, for the command line
+pub const SEARCH_OPTIONS: &[&str] = &[
+    "move-ordering",
+    "quiescence",
+    "null-move",
+    "lmr",
+    "reverse-futility",
+    "futility",
+];
+
+impl SearchOptions {
+    // Only what can't change the score: with these, the search has to come up with the same
+    // scores as plain minimax would
+    pub fn exact() -> Self {
+        SearchOptions {
+            quiescence: false,
+            null_move: false,
+            late_move_reductions: false,
+            reverse_futility: false,
+            futility: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn set(&mut self, name: &str, on: bool) -> Option<()> {
+        let option = match name {
+            "move-ordering" => &mut self.move_ordering,
+            "quiescence" => &mut self.quiescence,
+            "null-move" => &mut self.null_move,
+            "lmr" => &mut self.late_move_reductions,
+            "reverse-futility" => &mut self.reverse_futility,
+            "futility" => &mut self.futility,
+            _ => return None,
+        };
+        *option = on;
+        Some(())
+    }
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            move_ordering: true,
+            quiescence: true,
+            null_move: true,
+            late_move_reductions: true,
+            reverse_futility: true,
+            futility: true,
+        }
+    }
+}
+
+struct AbortConditions {
+    deadline: Option<Instant>,
+    nodes: Option<i64>,
+}
+
 pub struct IDAB<Ev: Evaluator> {
     pub evaluator: Ev,
     pub searched_positions: i64,
     pub move_buffers: Vec<[Move; MAX_MOVES]>,
+    pub tt: TranspositionTable,
+    // Hashes of the positions played before the root, oldest first, to spot repetitions.
+    // The search pushes the current line onto it and pops it back off as it goes.
+    pub history: Vec<u64>,
+    pub options: SearchOptions,
+    pub ordering: MoveOrdering,
+    pv_table: Vec<Vec<Move>>,
+    // Whether the position at each ply was reached by passing
+    after_null_move: Vec<bool>,
+    // Tried first at the root, usually the best move from the previous iteration
+    root_hint: Option<Move>,
+    stop: Arc<AtomicBool>,
+    abort_when: Option<AbortConditions>,
+    aborted: bool,
 }
 
 impl<Ev: Evaluator> IDAB<Ev> {
     pub fn new(evaluator: Ev) -> IDAB<Ev> {
+        Self::with_tt_size(evaluator, DEFAULT_TT_SIZE_MB)
+    }
+
+    pub fn with_tt_size(evaluator: Ev, tt_size_mb: usize) -> IDAB<Ev> {
         IDAB {
             evaluator,
             searched_positions: 0,
             move_buffers: Vec::new(),
+            tt: TranspositionTable::new(tt_size_mb),
+            history: Vec::new(),
+            options: SearchOptions::default(),
+            ordering: MoveOrdering::new(),
+            pv_table: Vec::new(),
+            after_null_move: Vec::new(),
+            root_hint: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            abort_when: None,
+            aborted: false,
         }
     }
 
+    // Setting this flag from another thread makes `iterate` wrap up as soon as possible
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    // From White's point of view, like the window; `player` is the side to move in `pos`
     pub fn evaluate(
         &mut self,
         pos: Board,
         player: Color,
         depth: u64,
-        mut alpha: i64,
-        mut beta: i64,
+        alpha: i64,
+        beta: i64,
     ) -> i64 {
+        // i64::MIN can't be negated, so the window gets narrowed by one
+        let alpha = alpha.max(-INFINITY);
+        match player {
+            Color::White => self.negamax(pos, depth, 0, alpha, beta),
+            Color::Black => -self.negamax(pos, depth, 0, -beta, -alpha),
+        }
+    }
+
+    pub fn search(&mut self, pos: Board, depth: u64) -> SearchResult {
+        self.abort_when = None;
+        self.root_hint = None;
+        self.ordering.clear();
+        self.search_depth(pos, depth)
+    }
+
+    fn search_depth(&mut self, pos: Board, depth: u64) -> SearchResult {
+        let nodes_before = self.searched_positions;
+        self.aborted = false;
+        let score = self.negamax(pos, depth, 0, -INFINITY, INFINITY);
+        let pv = self.pv_table[0].clone();
+        SearchResult {
+            best_move: pv.first().copied(),
+            score: Score(match pos.player {
+                Color::White => score,
+                Color::Black => -score,
+            }),
+            pv,
+            depth,
+            nodes: self.searched_positions - nodes_before,
+        }
+    }
+
+    // Iterative deepening: searches depth 1, 2, ... until we run out of depth, nodes or
+    // time, or someone raises the stop flag. `report` is called after every completed
+    // iteration with the time spent so far. The first iteration always runs to completion,
+    // so there is always a move to play (unless there are no moves at all).
+    pub fn iterate(
+        &mut self,
+        pos: Board,
+        limits: &SearchLimits,
+        mut report: impl FnMut(&SearchResult, Duration),
+    ) -> SearchResult {
+        let start = Instant::now();
+        let budget = limits.time_budget();
+        let max_depth = limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        self.searched_positions = 0;
+        self.root_hint = None;
+        self.abort_when = None;
+        self.ordering.clear();
+
+        let mut best = self.search_depth(pos, 1);
+        report(&best, start.elapsed());
+        self.abort_when = Some(AbortConditions {
+            deadline: budget.map(|(_, hard)| start + hard),
+            nodes: limits.nodes,
+        });
+        for depth in 2..=max_depth {
+            let out_of_time = budget.is_some_and(|(soft, _)| start.elapsed() >= soft);
+            let out_of_nodes = limits.nodes.is_some_and(|n| self.searched_positions >= n);
+            if out_of_time || out_of_nodes || self.stop.load(Ordering::Relaxed) {
+                break;
+            }
+            self.root_hint = best.best_move;
+            let mut result = self.search_depth(pos, depth);
+            if self.aborted {
+                // Whatever we found is based on a partial search; stick with what we know
+                break;
+            }
+            // Count everything we've searched so far, not just this iteration
+            result.nodes = self.searched_positions;
+            report(&result, start.elapsed());
+            best = result;
+        }
+        self.abort_when = None;
+        best.nodes = self.searched_positions;
+        best
+    }
+
+    fn should_abort(&mut self) -> bool {
+        if self.aborted {
+            return true;
+        }
+        let Some(abort_when) = &self.abort_when else {
+            return false;
+        };
+        if abort_when
+            .nodes
+            .is_some_and(|n| self.searched_positions >= n)
+        {
+            self.aborted = true
+        } else if self.searched_positions % CLOCK_CHECK_INTERVAL == 0 {
+            self.aborted = self.stop.load(Ordering::Relaxed)
+                || abort_when.deadline.is_some_and(|d| Instant::now() >= d)
+        }
+        self.aborted
+    }
+
+    // Any repetition at all counts as a draw here: if it was good for us the first time
+    // around, the opponent will avoid it, and if it wasn't then neither will we.
+    fn is_draw(&self, pos: &Board) -> bool {
+        if pos.is_insufficient_material() {
+            return true;
+        }
+        // Unless the move that gets there is mate
+        if pos.half_moves >= FIFTY_MOVE_RULE {
+            return !pos.is_checkmate();
+        }
+        let reversible = (pos.half_moves as usize).min(self.history.len());
+        self.history[self.history.len() - reversible..]
+            .iter()
+            .rev()
+            .skip(1)
+            .step_by(2)
+            .any(|hash| *hash == pos.hash)
+    }
+
+    // The evaluator works from White's point of view, the search from the side to move's
+    fn static_eval(&mut self, pos: &Board) -> i64 {
+        let eval = self.evaluator.evaluate(pos);
+        match pos.player {
+            Color::White => eval,
+            Color::Black => -eval,
+        }
+    }
+
+    // Scores are from the point of view of the side to move, so each side's best is the
+    // other side's worst and a child's score only needs negating. After the first move,
+    // which is likely the best one thanks to move ordering, every other move only gets a
+    // null window search to prove it's no better (principal variation search). The few
+    // that turn out better get searched again with the full window.
+    fn negamax(&mut self, pos: Board, depth: u64, ply: usize, mut alpha: i64, beta: i64) -> i64 {
+        // Triangular PV table: row `ply` holds the best line found from this node on. The
+        // parent reads it even when we give up straight away, so it has to be there.
+        if self.pv_table.len() <= ply {
+            self.pv_table.resize_with(ply + 1, Vec::new);
+        }
+        self.pv_table[ply].clear();
+        if self.should_abort() {
+            // The result is getting thrown away anyway
+            return 0;
+        }
         self.searched_positions += 1;
+        // Never at the root though, we still want a move to play
+        if ply > 0 && self.is_draw(&pos) {
+            return 0;
+        }
         if depth == 0 {
-            self.evaluator.evaluate(&pos)
+            return if self.options.quiescence {
+                self.quiescence(pos, ply, alpha, beta)
+            } else {
+                self.static_eval(&pos)
+            };
+        }
+
+        let alpha_orig = alpha;
+        let mut hash_move = None;
+        if let Some(entry) = self.tt.probe(pos.hash) {
+            hash_move = entry.best_move;
+            // Never cut at the root, we want a move and a full PV out of it
+            if ply > 0 && entry.depth as u64 >= depth {
+                let score = score_from_tt(entry.score, ply);
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if cutoff {
+                    let best_move = entry.best_move;
+                    self.pv_table[ply].extend(best_move);
+                    return score;
+                }
+            }
+        }
+
+        let in_check = pos.in_check(pos.player);
+        // Off the PV, nobody cares about exact scores, only about which side of the window
+        // they are on, and that's where the static evaluation can save us some work
+        let pv_node = alpha + 1 < beta;
+        let static_eval = if ply > 0 && !pv_node && !in_check {
+            Some(self.static_eval(&pos))
+        } else {
+            None
+        };
+        if let Some(eval) = static_eval {
+            if let Some(score) = self.prune(pos, eval, depth, ply, beta) {
+                return score;
+            }
+        }
+        // The most a quiet move could hope to get us here, when that's not enough to reach
+        // alpha. Skipped moves still count as having scored that much, or we'd be claiming
+        // the node is worse than we've shown it to be.
+        let futility_bound = match (static_eval, FUTILITY_MARGINS.get(depth as usize)) {
+            (Some(eval), Some(margin)) if self.options.futility => {
+                Some(eval + margin).filter(|bound| *bound <= alpha && !Score(alpha).is_mate())
+            }
+            _ => None,
+        };
+
+        let mut moves = Vec::with_capacity(32);
+        pos.legal_moves(&mut moves);
+        if moves.is_empty() {
+            return if in_check { mated(ply) } else { 0 };
+        }
+        let first = if ply == 0 {
+            self.root_hint.or(hash_move)
+        } else {
+            hash_move
+        };
+        let picker = if self.options.move_ordering {
+            MovePicker::new(&pos, &moves, first, &self.ordering, ply)
         } else {
-            let mut best = match player {
-                Color::Black => i64::max_value(),
-                Color::White => i64::min_value(),
+            MovePicker::unordered(&moves, first)
+        };
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        self.history.push(pos.hash);
+        for (i, mv) in picker.enumerate() {
+            let new_pos = pos.apply(&mv);
+            // Captures, promotions and checks are never pruned or reduced
+            let quiet = !is_tactical(&pos, &mv) && !new_pos.in_check(new_pos.player);
+            if i > 0 && quiet {
+                if let Some(bound) = futility_bound {
+                    best = max(best, bound);
+                    continue;
+                }
+            }
+            let reduction = if i > 0 && quiet && !in_check {
+                self.reduction(&pos, mv, i, depth, ply)
+            } else {
+                0
             };
-            let mut moves = Vec::with_capacity(32);
-            pos.pre_legal_moves(&mut moves);
-            for mv in moves.iter() {
-                let new_pos = pos.apply(&mv);
-                let score = self.evaluate(new_pos, player.opponent(), depth - 1, alpha, beta);
-
-                if player == Color::White {
-                    best = max(score, best);
-                    if best >= beta {
-                        break;
-                    };
-                    alpha = max(alpha, score)
+            self.evaluator.make_move(&pos, &new_pos);
+            let score = if i == 0 {
+                -self.negamax(new_pos, depth - 1, ply + 1, -beta, -alpha)
+            } else {
+                let mut score =
+                    -self.negamax(new_pos, depth - 1 - reduction, ply + 1, -alpha - 1, -alpha);
+                if reduction > 0 && score > alpha {
+                    score = -self.negamax(new_pos, depth - 1, ply + 1, -alpha - 1, -alpha);
+                }
+                if score > alpha && score < beta {
+                    -self.negamax(new_pos, depth - 1, ply + 1, -beta, -alpha)
                 } else {
-                    best = min(score, best);
-                    if best <= alpha {
-                        break;
-                    };
-                    beta = min(beta, score)
+                    score
                 }
+            };
+            self.evaluator.unmake_move();
+            // Whatever the child came back with is made up, it can't be our best move
+            if self.aborted {
+                break;
+            }
+
+            if score > best {
+                best = score;
+                best_move = Some(mv);
+                self.update_pv(ply, mv);
+            }
+            if score >= beta {
+                self.ordering.cutoff(&pos, mv, depth, ply);
+                break;
             }
-            best
+            alpha = max(alpha, score);
+        }
+        self.history.pop();
+        // Only some of the moves got searched, so `best` is no score at all, and it
+        // mustn't end up in the TT where the next iteration would trust it
+        if self.aborted {
+            return 0;
+        }
+
+        let bound = if best <= alpha_orig {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.tt.store(Entry {
+            key: pos.hash,
+            depth: depth as u8,
+            bound,
+            score: score_to_tt(best, ply),
+            best_move,
+        });
+        best
+    }
+
+    // Reverse futility and null move pruning: if the static evaluation says the side to
+    // move is doing well enough already, the score it returns (if any) is good enough to
+    // cut off with without looking at any moves
+    fn prune(&mut self, pos: Board, eval: i64, depth: u64, ply: usize, beta: i64) -> Option<i64> {
+        if eval < beta || Score(beta).is_mate() {
+            return None;
+        }
+        let margin = REVERSE_FUTILITY_MARGIN * depth as i64;
+        if self.options.reverse_futility
+            && depth <= REVERSE_FUTILITY_MAX_DEPTH
+            && eval - margin >= beta
+        {
+            return Some(eval - margin);
+        }
+        // With nothing but pawns, being forced to move is often what loses (zugzwang), so
+        // passing would make us look better off than we are. Two passes in a row prove
+        // nothing either.
+        if self.after_null_move.len() <= ply + 1 {
+            self.after_null_move.resize(ply + 2, false);
         }
+        let pieces = pos[pos.player] & !(pos[Piece::Pawn] | pos[Piece::King]);
+        if !self.options.null_move
+            || depth < NULL_MOVE_MIN_DEPTH
+            || pieces.is_empty()
+            || self.after_null_move[ply]
+        {
+            return None;
+        }
+        let null_pos = pos.apply_null();
+        self.after_null_move[ply + 1] = true;
+        self.history.push(pos.hash);
+        self.evaluator.make_move(&pos, &null_pos);
+        let reduced = depth.saturating_sub(1 + NULL_MOVE_REDUCTION);
+        let score = -self.negamax(null_pos, reduced, ply + 1, -beta, -beta + 1);
+        self.evaluator.unmake_move();
+        self.history.pop();
+        self.after_null_move[ply + 1] = false;
+        // A mate found by passing isn't real, we can't actually pass
+        (score >= beta && !self.aborted).then(|| if Score(score).is_mate() { beta } else { score })
     }
 
-    /* Just for debugging purposes */
-    pub fn evaluate_naive(&mut self, pos: Board, player: Color, depth: u64) -> i64 {
-        self.searched_positions += 1;
-        if depth == 0 {
-            self.evaluator.evaluate(&pos)
+    // Late move reductions. Quiet moves that come this late after good move ordering are
+    // unlikely to be any good, unless they have a history of being good.
+    fn reduction(&self, pos: &Board, mv: Move, index: usize, depth: u64, ply: usize) -> u64 {
+        let killer = self.ordering.killers(ply).contains(&Some(mv));
+        if !self.options.late_move_reductions
+            || depth < LMR_MIN_DEPTH
+            || index < LMR_MIN_MOVES
+            || killer
+        {
+            return 0;
+        }
+        let mut reduction = if index >= LMR_LATE_MOVES && depth > LMR_MIN_DEPTH {
+            2
         } else {
-            let mut best = match player {
-                Color::Black => i64::max_value(),
-                Color::White => i64::min_value(),
+            1
+        };
+        if self.ordering.history(pos.player, &mv) >= LMR_GOOD_HISTORY {
+            reduction -= 1
+        }
+        reduction
+    }
+
+    // Captures and promotions only, until the position is quiet enough to trust the
+    // evaluator. Either side can stop capturing whenever it likes (stand pat), so the
+    // static evaluation is a lower bound on the score, unless we're in check and have to
+    // get out of it somehow. Nothing in here goes in the TT or the PV.
+    fn quiescence(&mut self, pos: Board, ply: usize, mut alpha: i64, beta: i64) -> i64 {
+        // negamax has already counted this node when it's the first one
+        if self.should_abort() {
+            return 0;
+        }
+
+        let in_check = pos.in_check(pos.player);
+        let mut moves = Vec::with_capacity(32);
+        let mut best;
+        let mut stand_pat = None;
+        if in_check {
+            pos.legal_moves(&mut moves);
+            if moves.is_empty() {
+                return mated(ply);
+            }
+            best = -INFINITY;
+        } else {
+            let eval = self.static_eval(&pos);
+            if eval >= beta {
+                return eval;
+            }
+            alpha = max(alpha, eval);
+            best = eval;
+            stand_pat = Some(eval);
+            pos.pre_legal_captures(&mut moves);
+        }
+
+        for mv in MovePicker::new(&pos, &moves, None, &self.ordering, ply) {
+            if let (Some(eval), Some(victim)) = (stand_pat, victim(&pos, &mv)) {
+                let attacker = attacker(&pos, &mv);
+                // Delta pruning: even winning the piece for free wouldn't make a difference.
+                // Promotions can win a lot more than that, so they always get a look.
+                let hopeless = eval + SEE_VALUES[victim as usize] + DELTA_MARGIN <= alpha;
+                let promotes = matches!(mv, Move::Simple(s) if s.piece != attacker);
+                // Nor are captures that lose material, we could just stand pat instead
+                if (hopeless && !promotes) || losing_capture(&pos, &mv, victim, attacker) {
+                    continue;
+                }
+            }
+            let new_pos = pos.apply(&mv);
+            if !in_check && new_pos.in_check(pos.player) {
+                continue;
+            }
+            self.searched_positions += 1;
+            self.evaluator.make_move(&pos, &new_pos);
+            let score = -self.quiescence(new_pos, ply + 1, -beta, -alpha);
+            self.evaluator.unmake_move();
+
+            best = max(best, score);
+            if best >= beta {
+                break;
+            }
+            alpha = max(alpha, score);
+        }
+        best
+    }
+
+    fn update_pv(&mut self, ply: usize, mv: Move) {
+        let (head, tail) = self.pv_table.split_at_mut(ply + 1);
+        let line = &mut head[ply];
+        line.clear();
+        line.push(mv);
+        line.extend_from_slice(&tail[0]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::moves::AlgebraicMove;
+    use crate::pst::PieceSquareTables;
+
+    // Plain minimax from White's point of view, with nothing clever in it at all. Far too
+    // slow to play with, but hard to get wrong, so the real search has to agree with it.
+    fn evaluate_naive(evaluator: &mut impl Evaluator, pos: Board, depth: u64, ply: usize) -> i64 {
+        // Same draws as the search, except repetitions, which can't happen this shallow
+        let fifty_moves = pos.half_moves >= FIFTY_MOVE_RULE && !pos.is_checkmate();
+        if ply > 0 && (pos.is_insufficient_material() || fifty_moves) {
+            return 0;
+        }
+        if depth == 0 {
+            return evaluator.evaluate(&pos);
+        }
+        let mut moves = Vec::with_capacity(32);
+        pos.legal_moves(&mut moves);
+        if moves.is_empty() {
+            let score = if pos.in_check(pos.player) {
+                mated(ply)
+            } else {
+                0
             };
-            let mut moves = Vec::with_capacity(32);
-            pos.pre_legal_moves(&mut moves);
-            for mv in moves.iter() {
-                let new_pos = pos.apply(&mv);
-                let score = self.evaluate_naive(new_pos, player.opponent(), depth - 1);
-
-                if player == Color::White {
-                    best = max(score, best);
-                } else {
-                    best = min(score, best);
+            return match pos.player {
+                Color::White => score,
+                Color::Black => -score,
+            };
+        }
+        let scores = moves
+            .iter()
+            .map(|mv| evaluate_naive(evaluator, pos.apply(mv), depth - 1, ply + 1));
+        match pos.player {
+            Color::White => scores.max().unwrap(),
+            Color::Black => scores.min().unwrap(),
+        }
+    }
+
+    fn check_against_naive(pos: Board, depth: u64) {
+        let mut search = IDAB::with_tt_size(PieceSquareTables::new(), 1);
+        search.options = SearchOptions::exact();
+        let result = search.search(pos, depth);
+        let naive = evaluate_naive(&mut PieceSquareTables::new(), pos, depth, 0);
+        assert_eq!(result.score, Score(naive), "{} at depth {depth}", pos.fen());
+    }
+
+    #[test]
+    fn search_agrees_with_minimax_along_games() {
+        for game_no in (1..=100).step_by(20) {
+            let moves = read_to_string(format!("games/game_{game_no}.pgn")).unwrap();
+            let mut board = Board::initial();
+            for (i, move_str) in moves.split_whitespace().enumerate() {
+                check_against_naive(board, 2);
+                if i % 10 == 0 {
+                    check_against_naive(board, 3);
                 }
+                let mv = board
+                    .is_legal(&AlgebraicMove::parse(move_str).unwrap())
+                    .unwrap();
+                board = board.apply(&mv);
             }
-            let eval_ab = self.evaluate(pos, player, depth, i64::min_value(), i64::max_value());
-            if eval_ab != best {
-                std::process::exit(-1);
+        }
+    }
+
+    #[test]
+    fn futility_pruning_keeps_bounds_sound() {
+        // A queen down with nothing but quiet moves, so near the leaves every move after
+        // the first is pruned when the window is above us
+        let pos = crate::fen::parse("q3k3/8/8/8/8/8/PPP5/K7 w - - 0 1")
+            .unwrap()
+            .board;
+        for depth in 1..=2 {
+            let search = |futility| {
+                let mut search = IDAB::with_tt_size(PieceSquareTables::new(), 1);
+                search.options = SearchOptions {
+                    futility,
+                    ..SearchOptions::exact()
+                };
+                // Off the PV, where futility pruning happens
+                let score = search.negamax(pos, depth, 1, 0, 1);
+                (score, search)
+            };
+            let (exact, exact_search) = search(false);
+            let (pruned, pruned_search) = search(true);
+            assert!(exact <= 0);
+            assert!(pruned_search.searched_positions < exact_search.searched_positions);
+            // Failing low means the score is an upper bound, which it isn't if it's below
+            // what the full search found, and neither is what goes in the TT
+            assert!(pruned >= exact, "{pruned} < {exact} at depth {depth}");
+            let entry = pruned_search.tt.probe(pos.hash).unwrap();
+            assert!(entry.bound == Bound::Upper);
+            assert!(score_from_tt(entry.score, 1) >= exact);
+        }
+    }
+
+    #[test]
+    fn search_agrees_with_minimax_on_mates() {
+        for fen in [
+            // Mate in one, and in two
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+            "6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1",
+            "k7/8/1K6/8/8/8/8/7R w - - 0 1",
+            // Stalemate traps
+            "7k/5Q2/6K1/8/8/8/8/8 w - - 0 1",
+        ] {
+            let pos = crate::fen::parse(fen).unwrap().board;
+            for depth in 1..=4 {
+                check_against_naive(pos, depth);
             }
-            best
         }
     }
 }
@@ -0,0 +1,129 @@
+use crate::moves::Move;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Bound {
+    Exact,
+    // The real score is at least this much
+    Lower,
+    // The real score is at most this much
+    Upper,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: u8,
+    pub bound: Bound,
+    pub score: i64,
+    pub best_move: Option<Move>,
+}
+
+pub const DEFAULT_TT_SIZE_MB: usize = 16;
+
+// Fixed-size, power-of-two table indexed by the low bits of the Zobrist key. A size of zero
+// disables it entirely, which is handy to get reproducible searches.
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    mask: u64,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let bytes = size_mb * 1024 * 1024;
+        let max_entries = bytes / std::mem::size_of::<Option<Entry>>();
+        let len = if max_entries == 0 {
+            0
+        } else {
+            // Round down to a power of two so we can mask instead of dividing
+            1 << max_entries.ilog2()
+        };
+        TranspositionTable {
+            entries: vec![None; len],
+            mask: (len as u64).wrapping_sub(1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None)
+    }
+
+    pub fn probe(&self, key: u64) -> Option<&Entry> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.entries[(key & self.mask) as usize]
+            .as_ref()
+            .filter(|entry| entry.key == key)
+    }
+
+    // Replaces whatever was in the slot, unless it's the same position searched deeper
+    pub fn store(&mut self, entry: Entry) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let slot = &mut self.entries[(entry.key & self.mask) as usize];
+        match slot {
+            Some(old) if old.key == entry.key && old.depth > entry.depth => (),
+            _ => *slot = Some(entry),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: u64, depth: u8) -> Entry {
+        Entry {
+            key,
+            depth,
+            bound: Bound::Exact,
+            score: depth as i64,
+            best_move: None,
+        }
+    }
+
+    #[test]
+    fn tt_size_is_power_of_two() {
+        for mb in [1, 3, 16, 100] {
+            let tt = TranspositionTable::new(mb);
+            assert!(tt.len().is_power_of_two());
+            assert!(tt.len() * std::mem::size_of::<Option<Entry>>() <= mb * 1024 * 1024);
+        }
+        assert!(TranspositionTable::new(0).is_empty());
+    }
+
+    #[test]
+    fn tt_store_and_replace() {
+        let mut tt = TranspositionTable::new(1);
+        let len = tt.len() as u64;
+        tt.store(entry(42, 5));
+        assert_eq!(tt.probe(42).unwrap().depth, 5);
+        // Same slot, different position
+        assert!(tt.probe(42 + len).is_none());
+        // Shallower results for the same position don't replace deeper ones...
+        tt.store(entry(42, 3));
+        assert_eq!(tt.probe(42).unwrap().depth, 5);
+        // ...but other positions always do
+        tt.store(entry(42 + len, 1));
+        assert!(tt.probe(42).is_none());
+        assert_eq!(tt.probe(42 + len).unwrap().depth, 1);
+        tt.clear();
+        assert!(tt.probe(42 + len).is_none());
+    }
+
+    #[test]
+    fn disabled_tt_stores_nothing() {
+        let mut tt = TranspositionTable::new(0);
+        tt.store(entry(42, 5));
+        assert!(tt.probe(42).is_none());
+    }
+}
@@ -0,0 +1,358 @@
+use std::io::BufRead;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::eval::{self, Weighted};
+use crate::fen;
+use crate::moves::{CoordinateMove, Move};
+use crate::piece::Color;
+use crate::search::{Score, SearchLimits, IDAB};
+use crate::tt::{TranspositionTable, DEFAULT_TT_SIZE_MB};
+
+const ENGINE_NAME: &str = "chess-for-crabs";
+const ENGINE_AUTHOR: &str = "m-alvarez";
+const MAX_TT_SIZE_MB: usize = 4096;
+
+#[derive(Default)]
+struct GoParams {
+    limits: SearchLimits,
+    infinite: bool,
+}
+
+impl GoParams {
+    fn parse<'a>(player: Color, mut tokens: impl Iterator<Item = &'a str>) -> GoParams {
+        let mut params = GoParams::default();
+        let limits = &mut params.limits;
+        let millis = |ms: Option<u64>| ms.map(Duration::from_millis);
+        while let Some(tok) = tokens.next() {
+            let mut next_number = || tokens.next().and_then(|v| v.parse::<u64>().ok());
+            match (tok, player) {
+                ("depth", _) => limits.depth = next_number(),
+                ("nodes", _) => limits.nodes = next_number().map(|n| n as i64),
+                ("movetime", _) => limits.movetime = millis(next_number()),
+                ("movestogo", _) => limits.moves_to_go = next_number(),
+                ("wtime", Color::White) | ("btime", Color::Black) => {
+                    limits.time_left = millis(next_number())
+                }
+                ("winc", Color::White) | ("binc", Color::Black) => {
+                    limits.increment = millis(next_number())
+                }
+                // The other side's clock is none of our business
+                ("wtime" | "btime" | "winc" | "binc", _) => {
+                    next_number();
+                }
+                ("infinite", _) => params.infinite = true,
+                // Unsupported (searchmoves, ponder, mate...), ignore it
+                _ => (),
+            }
+        }
+        params
+    }
+}
+
+// UCI wants scores from the engine's point of view, ours are White's
+fn format_score(player: Color, score: Score) -> String {
+    let score = match player {
+        Color::White => score,
+        Color::Black => -score,
+    };
+    match score.mate_in() {
+        Some(moves) => format!("mate {moves}"),
+        None => format!("cp {}", score.0),
+    }
+}
+
+// Anything bigger than we can handle gets as big as we can handle
+fn hash_size_mb(value: &str) -> Option<usize> {
+    Some(value.parse::<usize>().ok()?.min(MAX_TT_SIZE_MB))
+}
+
+fn format_pv(board: &Board, pv: &[Move]) -> String {
+    let mut board = *board;
+    let mut line = Vec::with_capacity(pv.len());
+    for mv in pv {
+        line.push(board.to_coordinate(*mv).to_string());
+        board = board.apply(mv)
+    }
+    line.join(" ")
+}
+
+fn think(search: &Mutex<Engine>, board: Board, history: Vec<u64>, params: GoParams) {
+    let mut search = search.lock().unwrap();
+    search.history = history;
+    let result = search.iterate(board, &params.limits, |result, elapsed| {
+        let elapsed = elapsed.as_millis() as i64;
+        println!(
+            "info depth {} score {} nodes {} time {elapsed} nps {} pv {}",
+            result.depth,
+            format_score(board.player, result.score),
+            result.nodes,
+            result.nodes * 1000 / elapsed.max(1),
+            format_pv(&board, &result.pv)
+        );
+    });
+    // We're not allowed to answer an infinite search until we're told to stop
+    let stop = search.stop_handle();
+    while params.infinite && !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    match result.best_move {
+        Some(mv) => println!("bestmove {}", board.to_coordinate(mv)),
+        None => println!("bestmove 0000"),
+    }
+}
+
+type Engine = IDAB<Weighted>;
+
+pub struct Uci {
+    board: Board,
+    // Positions leading up to `board`, for repetitions
+    history: Vec<u64>,
+    search: Arc<Mutex<Engine>>,
+    stop: Arc<AtomicBool>,
+    thinking: Option<JoinHandle<()>>,
+}
+
+impl Uci {
+    pub fn new() -> Self {
+        Self::with_evaluator(eval::by_name(eval::DEFAULT_EVALUATOR).unwrap())
+    }
+
+    pub fn with_evaluator(evaluator: Weighted) -> Self {
+        let search = IDAB::new(evaluator);
+        Uci {
+            board: Board::initial(),
+            history: Vec::new(),
+            stop: search.stop_handle(),
+            search: Arc::new(Mutex::new(search)),
+            thinking: None,
+        }
+    }
+
+    fn stop_thinking(&mut self) {
+        if let Some(handle) = self.thinking.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            handle.join().unwrap();
+        }
+    }
+
+    fn set_position<'a>(&mut self, mut tokens: impl Iterator<Item = &'a str>) -> Option<()> {
+        let mut board = match tokens.next()? {
+            "startpos" => Board::initial(),
+            "fen" => {
+                let fields: Vec<&str> = tokens.by_ref().take_while(|tok| *tok != "moves").collect();
+                fen::parse(&fields.join(" "))?.board
+            }
+            _ => return None,
+        };
+        let mut history = Vec::new();
+        // The FEN branch has already eaten the "moves" keyword
+        for tok in tokens.skip_while(|tok| *tok == "moves") {
+            let mv = board.from_coordinate(&CoordinateMove::parse(tok)?)?;
+            history.push(board.hash);
+            board = board.apply(&mv)
+        }
+        self.board = board;
+        self.history = history;
+        Some(())
+    }
+
+    // setoption name <name> value <value>
+    fn set_option<'a>(&mut self, mut tokens: impl Iterator<Item = &'a str>) -> Option<()> {
+        if tokens.next()? != "name" {
+            return None;
+        }
+        let name: Vec<&str> = tokens.by_ref().take_while(|tok| *tok != "value").collect();
+        let value: Vec<&str> = tokens.collect();
+        match (name.join(" ").as_str(), value.as_slice()) {
+            ("Hash", [size]) => {
+                self.search.lock().unwrap().tt = TranspositionTable::new(hash_size_mb(size)?);
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    // Returns false once we've been told to quit
+    pub fn handle(&mut self, line: &str) -> bool {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("uci") => {
+                println!("id name {ENGINE_NAME}");
+                println!("id author {ENGINE_AUTHOR}");
+                println!(
+                    "option name Hash type spin default {DEFAULT_TT_SIZE_MB} min 0 max {MAX_TT_SIZE_MB}"
+                );
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => {
+                self.stop_thinking();
+                self.board = Board::initial();
+                self.history.clear();
+                self.search.lock().unwrap().tt.clear()
+            }
+            Some("setoption") => {
+                self.stop_thinking();
+                if self.set_option(tokens).is_none() {
+                    println!("info string unsupported option: {line}")
+                }
+            }
+            Some("position") => {
+                self.stop_thinking();
+                if self.set_position(tokens).is_none() {
+                    println!("info string invalid position: {line}")
+                }
+            }
+            Some("go") => {
+                self.stop_thinking();
+                self.stop.store(false, Ordering::Relaxed);
+                let params = GoParams::parse(self.board.player, tokens);
+                let (board, history) = (self.board, self.history.clone());
+                let search = self.search.clone();
+                self.thinking = Some(std::thread::spawn(move || {
+                    think(&search, board, history, params)
+                }));
+            }
+            Some("stop") => self.stop_thinking(),
+            Some("quit") => {
+                self.stop_thinking();
+                return false;
+            }
+            // The protocol says to ignore anything we don't understand
+            _ => (),
+        }
+        true
+    }
+
+    pub fn run(&mut self, input: impl BufRead) -> std::io::Result<()> {
+        for line in input.lines() {
+            if !self.handle(&line?) {
+                break;
+            }
+        }
+        self.stop_thinking();
+        Ok(())
+    }
+}
+
+impl Default for Uci {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MATE;
+
+    fn uci() -> Uci {
+        Uci::with_evaluator(eval::by_name("material").unwrap())
+    }
+
+    fn play(mut board: Board, moves: &str) -> (Board, Vec<u64>) {
+        let mut history = Vec::new();
+        for tok in moves.split_whitespace() {
+            let mv = board
+                .from_coordinate(&CoordinateMove::parse(tok).unwrap())
+                .unwrap();
+            history.push(board.hash);
+            board = board.apply(&mv);
+        }
+        (board, history)
+    }
+
+    fn assert_position(uci: &Uci, board: Board, history: &[u64]) {
+        assert_eq!(uci.board.fen().to_string(), board.fen().to_string());
+        assert_eq!(uci.history, history);
+    }
+
+    #[test]
+    fn go_params() {
+        let go = |player, line: &str| GoParams::parse(player, line.split_whitespace());
+        let clocks = "wtime 60000 btime 30000 winc 1000 binc 500 movestogo 20";
+        let white = go(Color::White, clocks).limits;
+        assert_eq!(white.time_left, Some(Duration::from_secs(60)));
+        assert_eq!(white.increment, Some(Duration::from_secs(1)));
+        assert_eq!(white.moves_to_go, Some(20));
+        let black = go(Color::Black, clocks).limits;
+        assert_eq!(black.time_left, Some(Duration::from_secs(30)));
+        assert_eq!(black.increment, Some(Duration::from_millis(500)));
+        assert_eq!(black.moves_to_go, Some(20));
+        // The other side's clock isn't ours, and its numbers don't end up anywhere else
+        let params = go(Color::White, "btime 30000 binc 500 depth 5 nodes 1000");
+        assert!(params.limits.time_left.is_none() && params.limits.increment.is_none());
+        assert_eq!(params.limits.depth, Some(5));
+        assert_eq!(params.limits.nodes, Some(1000));
+        assert!(!params.infinite);
+        let params = go(Color::Black, "infinite");
+        assert!(params.infinite);
+        assert!(params.limits.depth.is_none() && params.limits.time_left.is_none());
+        let params = go(Color::White, "ponder movetime 250 searchmoves e2e4");
+        assert_eq!(params.limits.movetime, Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn set_position() {
+        let mut uci = uci();
+        uci.handle("position startpos moves e2e4 e7e5 g1f3");
+        let (board, history) = play(Board::initial(), "e2e4 e7e5 g1f3");
+        assert_position(&uci, board, &history);
+        uci.handle("position startpos");
+        assert_position(&uci, Board::initial(), &[]);
+
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+        let start = fen::parse(fen).unwrap().board;
+        uci.handle(&format!("position fen {fen}"));
+        assert_position(&uci, start, &[]);
+        uci.handle(&format!("position fen {fen} moves e2e4 e8d7"));
+        let (board, history) = play(start, "e2e4 e8d7");
+        assert_position(&uci, board, &history);
+
+        // One bad move and the whole command is ignored
+        for line in [
+            "position startpos moves e2e4 e2e4",
+            "position startpos moves e2e4 e7e9",
+            "position fen nonsense moves e2e4",
+            "position somewhere",
+        ] {
+            uci.handle(line);
+            assert_position(&uci, board, &history);
+        }
+
+        // Everything before the current position is there to spot repetitions with
+        uci.handle("position startpos moves g1f3 g8f6 f3g1 f6g8");
+        assert_eq!(uci.history.len(), 4);
+        assert_eq!(uci.history[0], uci.board.hash);
+        assert_eq!(uci.board.hash, Board::initial().hash);
+    }
+
+    #[test]
+    fn set_hash_size() {
+        let mut uci = uci();
+        uci.handle("setoption name Hash value 2");
+        let len = TranspositionTable::new(2).len();
+        assert_eq!(uci.search.lock().unwrap().tt.len(), len);
+        // Nonsense leaves the table alone
+        uci.handle("setoption name Hash value lots");
+        uci.handle("setoption name Hash");
+        assert_eq!(uci.search.lock().unwrap().tt.len(), len);
+        assert_eq!(hash_size_mb("16"), Some(16));
+        assert_eq!(hash_size_mb("100000"), Some(MAX_TT_SIZE_MB));
+        assert_eq!(hash_size_mb("-1"), None);
+    }
+
+    #[test]
+    fn scores_from_the_side_to_move() {
+        assert_eq!(format_score(Color::White, Score(35)), "cp 35");
+        assert_eq!(format_score(Color::Black, Score(35)), "cp -35");
+        // White mates in two
+        assert_eq!(format_score(Color::White, Score(MATE - 3)), "mate 2");
+        assert_eq!(format_score(Color::Black, Score(MATE - 3)), "mate -2");
+        assert_eq!(format_score(Color::Black, Score(-MATE + 1)), "mate 1");
+    }
+}
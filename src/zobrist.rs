@@ -0,0 +1,94 @@
+use crate::bitboard::Bitboard;
+use crate::board::{Bitboards, Board};
+use crate::piece::{Color, Piece};
+
+pub struct ZobristKeys {
+    // Indexed by color, piece and bit index
+    pub pieces: [[[u64; 64]; 6]; 2],
+    pub black_to_move: u64,
+    pub castling: [u64; 16],
+    // Indexed by file, the last entry (NO_EN_PASSANT) is zero
+    pub en_passant: [u64; 9],
+}
+
+// SplitMix64, which is more than good enough for this and easy to run at compile time
+const fn next_key(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+const fn precompute_keys() -> ZobristKeys {
+    let mut state = 0xC0FFEE;
+    let mut keys = ZobristKeys {
+        pieces: [[[0; 64]; 6]; 2],
+        black_to_move: 0,
+        castling: [0; 16],
+        en_passant: [0; 9],
+    };
+    const_for!(color in 0 .. 2 => {
+        const_for!(piece in 0 .. 6 => {
+            const_for!(idx in 0 .. 64 => {
+                keys.pieces[color][piece][idx] = next_key(&mut state);
+            });
+        });
+    });
+    keys.black_to_move = next_key(&mut state);
+    const_for!(rights in 0 .. 16 => {
+        keys.castling[rights] = next_key(&mut state);
+    });
+    const_for!(file in 0 .. 8 => {
+        keys.en_passant[file] = next_key(&mut state);
+    });
+    keys
+}
+
+pub static ZOBRIST: ZobristKeys = precompute_keys();
+
+impl ZobristKeys {
+    pub fn piece(&self, color: Color, piece: Piece, square: Bitboard) -> u64 {
+        self.pieces[color as usize][piece as usize][square.to_index()]
+    }
+
+    pub fn side(&self, player: Color) -> u64 {
+        match player {
+            Color::White => 0,
+            Color::Black => self.black_to_move,
+        }
+    }
+
+    // Hash of the pieces that differ between two piece placements. Moves only touch a handful of
+    // squares, so this is much cheaper than hashing both placements from scratch.
+    pub fn placement_delta(&self, old: &Bitboards, new: &Bitboards) -> u64 {
+        let mut hash = 0;
+        for color in Color::list() {
+            for piece in Piece::list() {
+                let before = old.0[*color as usize] & old.0[2 + *piece as usize];
+                let after = new.0[*color as usize] & new.0[2 + *piece as usize];
+                for square in (before ^ after).occupied() {
+                    hash ^= self.piece(*color, *piece, square)
+                }
+            }
+        }
+        hash
+    }
+}
+
+impl Board {
+    // The slow way, used to set up boards and to check the incremental updates
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+        for color in Color::list() {
+            for piece in Piece::list() {
+                for square in (self[*color] & self[*piece]).occupied() {
+                    hash ^= ZOBRIST.piece(*color, *piece, square)
+                }
+            }
+        }
+        hash ^ ZOBRIST.side(self.player)
+            ^ ZOBRIST.castling[self.castling_rights as usize]
+            ^ ZOBRIST.en_passant[self.en_passant as usize]
+    }
+}
@@ -0,0 +1,104 @@
+use board::{Board, IllegalMove};
+use chess_for_crabs::*;
+use moves::AlgebraicMove;
+
+fn play(board: Board, moves: &str) -> Board {
+    let mut board = board;
+    for move_str in moves.split_whitespace() {
+        let alg = AlgebraicMove::parse(move_str).unwrap();
+        let mv = board.is_legal(&alg).unwrap();
+        board = board.apply(&mv);
+        assert_eq!(board.hash, board.compute_hash(), "after {move_str}");
+    }
+    board
+}
+
+fn castle(board: &Board, castle: &str) -> Result<moves::Move, IllegalMove> {
+    board.is_legal(&AlgebraicMove::parse(castle).unwrap())
+}
+
+fn rights(board: &Board) -> String {
+    board
+        .fen()
+        .to_string()
+        .split(' ')
+        .nth(2)
+        .unwrap()
+        .to_string()
+}
+
+const OPEN_BACK_RANKS: &str = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+
+#[test]
+fn king_moves_lose_both_rights() {
+    let start = fen::parse(OPEN_BACK_RANKS).unwrap().board;
+    let board = play(start, "Kf1 Kf8 Ke1 Ke8");
+    assert_eq!(rights(&board), "-");
+    assert!(!board.castling_allowed(piece::Color::White));
+    for side in ["O-O", "O-O-O"] {
+        assert_eq!(
+            castle(&board, side),
+            Err(IllegalMove::NoCastlingPermissions)
+        );
+    }
+    // Same position, different rights, different hash
+    assert!(board.bitboards == start.bitboards);
+    assert_ne!(board.hash, start.hash);
+}
+
+#[test]
+fn rook_moves_lose_one_right() {
+    let start = fen::parse(OPEN_BACK_RANKS).unwrap().board;
+    let board = play(start, "Rg1 Rb8 Rh1 Ra8");
+    assert_eq!(rights(&board), "Qk");
+    assert_eq!(
+        castle(&board, "O-O"),
+        Err(IllegalMove::NoCastlingPermissions)
+    );
+    assert!(castle(&board, "O-O-O").is_ok());
+    let board = play(board, "Rb1");
+    assert_eq!(castle(&board, "O-O"), Ok(moves::Move::CastleShort));
+    assert_eq!(
+        castle(&board, "O-O-O"),
+        Err(IllegalMove::NoCastlingPermissions)
+    );
+    assert_eq!(rights(&board), "k");
+}
+
+#[test]
+fn capturing_a_rook_at_home_loses_its_right() {
+    let start = fen::parse("r3k2r/8/8/8/8/8/6B1/R3K2R w KQkq - 0 1")
+        .unwrap()
+        .board;
+    let board = play(start, "Bxa8");
+    assert_eq!(rights(&board), "KQk");
+    assert_eq!(
+        castle(&board, "O-O-O"),
+        Err(IllegalMove::NoCastlingPermissions)
+    );
+    // Both sides lose the queenside right, one for moving and one for being captured
+    let board = play(start, "Rxa8+");
+    assert_eq!(rights(&board), "Kk");
+    // A rook that comes back to the corner doesn't bring the right with it
+    let board = play(start, "Rxa8+ Kd7 Ra1 Ke8");
+    assert_eq!(rights(&board), "K");
+    assert_eq!(
+        castle(&board, "O-O-O"),
+        Err(IllegalMove::NoCastlingPermissions)
+    );
+}
+
+#[test]
+fn castling_loses_both_rights() {
+    let board = play(Board::initial(), "e4 e5 Nf3 Nf6 Bc4 Bc5 O-O");
+    assert_eq!(rights(&board), "kq");
+    let board = play(board, "O-O");
+    assert_eq!(rights(&board), "-");
+}
+
+#[test]
+fn rights_survive_unrelated_moves() {
+    let board = play(Board::initial(), "Nf3 Nf6 Ng1 Ng8");
+    assert_eq!(rights(&board), "KQkq");
+    assert_eq!(board.hash, Board::initial().hash);
+}
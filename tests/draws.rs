@@ -0,0 +1,126 @@
+use board::Board;
+use chess_for_crabs::*;
+use eval::MaterialCount;
+use game::{Draw, Game};
+use moves::AlgebraicMove;
+use search::{Score, IDAB};
+
+fn play(game: &mut Game, moves: &str) {
+    for move_str in moves.split_whitespace() {
+        let alg = AlgebraicMove::parse(move_str).unwrap();
+        let mv = game.board.is_legal(&alg).unwrap();
+        game.make_move(&alg, &mv);
+    }
+}
+
+fn board(fen: &str) -> Board {
+    fen::parse(fen).unwrap().board
+}
+
+#[test]
+fn insufficient_material() {
+    let draws = [
+        "8/8/8/4k3/8/8/8/4K3 w - - 0 1",
+        "8/8/8/4k3/8/8/8/4KB2 w - - 0 1",
+        "8/8/8/4k3/8/8/8/4K1n1 b - - 0 1",
+        // Bishops all on dark squares, however many
+        "8/8/8/4k3/8/2b5/8/2B1K3 w - - 0 1",
+        "8/8/8/4k3/8/8/3B4/2B1K3 w - - 0 1",
+    ];
+    for fen in draws {
+        assert!(board(fen).is_insufficient_material(), "{fen}");
+    }
+    let not_draws = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        // Opposite coloured bishops can still mate, with some help
+        "8/8/8/4k3/8/8/2b5/2B1K3 w - - 0 1",
+        "8/8/8/4k3/8/8/8/1NB1K3 w - - 0 1",
+        "8/8/8/4k3/8/8/8/1NN1K3 w - - 0 1",
+        "8/8/8/4k3/8/8/P7/4K3 w - - 0 1",
+        "8/8/8/4k3/8/8/8/R3K3 w - - 0 1",
+    ];
+    for fen in not_draws {
+        assert!(!board(fen).is_insufficient_material(), "{fen}");
+    }
+}
+
+#[test]
+fn threefold_repetition() {
+    let mut game = Game::new();
+    play(&mut game, "Nf3 Nf6 Ng1 Ng8");
+    assert_eq!(game.repetitions(), 2);
+    assert_eq!(game.draw(), None);
+    play(&mut game, "Nc3 Nc6 Nb1 Nb8");
+    assert_eq!(game.repetitions(), 3);
+    assert_eq!(game.draw(), Some(Draw::ThreefoldRepetition));
+    assert!(game.is_over());
+    game.undo_last_move();
+    assert_eq!(game.repetitions(), 1);
+    assert!(!game.is_over());
+}
+
+#[test]
+fn repetitions_need_the_same_castling_rights() {
+    // The kings walk out and back, which looks the same but isn't
+    let mut game = Game::new();
+    play(&mut game, "e4 e5 Ke2 Ke7 Ke1 Ke8");
+    assert_eq!(game.repetitions(), 1);
+    play(&mut game, "Ke2 Ke7 Ke1 Ke8");
+    assert_eq!(game.repetitions(), 2);
+}
+
+#[test]
+fn fifty_and_seventy_five_move_rules() {
+    let mut game = fen::parse("8/8/8/4k3/8/8/8/R3K3 w - - 98 80").unwrap();
+    play(&mut game, "Ra2");
+    assert_eq!(game.draw(), None);
+    play(&mut game, "Kd4");
+    assert_eq!(game.draw(), Some(Draw::FiftyMoveRule));
+    let mut game = fen::parse("8/8/8/4k3/8/8/8/R3K3 w - - 149 80").unwrap();
+    play(&mut game, "Ra2");
+    assert_eq!(game.draw(), Some(Draw::SeventyFiveMoveRule));
+    // Mating on the last move still wins
+    let mut game = fen::parse("4k3/8/4K3/8/8/8/8/R7 w - - 99 80").unwrap();
+    play(&mut game, "Ra8#");
+    assert!(game.board.is_checkmate());
+    assert_eq!(game.draw(), None);
+    assert!(game.is_over());
+}
+
+#[test]
+fn stalemate_and_material_end_the_game() {
+    let game = fen::parse("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    assert_eq!(game.draw(), Some(Draw::Stalemate));
+    let game = fen::parse("8/8/8/4k3/8/8/8/4KB2 w - - 0 1").unwrap();
+    assert_eq!(game.draw(), Some(Draw::InsufficientMaterial));
+}
+
+#[test]
+fn search_scores_draws_as_zero() {
+    let mut search = IDAB::new(MaterialCount());
+    // A whole bishop up, and still nothing to play for
+    let result = search.search(board("8/8/8/4k3/8/8/8/4KB2 w - - 0 1"), 3);
+    assert_eq!(result.score, Score(0));
+    // A queen up, but the clock is about to run out
+    let result = search.search(board("7k/8/8/8/8/8/8/Q3K3 w - - 99 80"), 3);
+    assert_eq!(result.score, Score(0));
+}
+
+#[test]
+fn search_takes_a_repetition_when_losing() {
+    // White is a queen down, but Kb1 repeats a position from earlier in the game
+    let root = board("k7/8/8/8/7q/8/8/K7 w - - 10 50");
+    let repeat = AlgebraicMove::parse("Kb1").unwrap();
+    let repeat = root.is_legal(&repeat).unwrap();
+    let mut search = IDAB::new(MaterialCount());
+    let repeated = root.apply(&repeat).hash;
+    search.history = vec![repeated];
+    let result = search.search(root, 1);
+    assert_eq!(result.score, Score(0));
+    assert_eq!(root.apply(&result.best_move.unwrap()).hash, repeated);
+    // Without the history it's just a lost position
+    search.history.clear();
+    search.tt.clear();
+    let result = search.search(root, 1);
+    assert_eq!(result.score, Score(-900));
+}
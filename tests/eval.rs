@@ -0,0 +1,309 @@
+use std::fs::read_to_string;
+
+use bitboard::Bitboard;
+use board::Board;
+use chess_for_crabs::*;
+use eval::Evaluator;
+use king_safety::{king_attacks, king_shelter};
+use mobility::mobility;
+use moves::AlgebraicMove;
+use pawns::{pawn_score, PawnStructure, Pawns};
+use piece::{Color, Piece};
+use pst::{PieceSquareTables, PstScore, MAX_PHASE};
+
+fn board(fen: &str) -> Board {
+    fen::parse(fen).unwrap().board
+}
+
+// The same position with the colours swapped, and the board flipped to match
+fn mirror(fen: &str) -> String {
+    let fields: Vec<&str> = fen.split(' ').collect();
+    let swap_case = |s: &str| {
+        s.chars()
+            .map(|c| match c {
+                'a'..='z' => c.to_ascii_uppercase(),
+                'A'..='Z' => c.to_ascii_lowercase(),
+                _ => c,
+            })
+            .collect::<String>()
+    };
+    let placement: Vec<&str> = fields[0].split('/').rev().collect();
+    let player = if fields[1] == "w" { "b" } else { "w" };
+    let mut castling: Vec<char> = swap_case(fields[2]).chars().collect();
+    castling.sort_by_key(|c| (c.is_ascii_lowercase(), *c == 'q' || *c == 'Q'));
+    let castling: String = castling.into_iter().collect();
+    let en_passant = fields[3]
+        .replace('3', "x")
+        .replace('6', "3")
+        .replace('x', "6");
+    format!(
+        "{} {player} {castling} {en_passant} {} {}",
+        swap_case(&placement.join("/")),
+        fields[4],
+        fields[5]
+    )
+}
+
+#[test]
+fn pst_is_symmetric() {
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+    ];
+    let mut pst = PieceSquareTables::new();
+    assert_eq!(pst.evaluate(&board(fens[0])), 0);
+    for fen in fens {
+        let mirrored = mirror(fen);
+        assert_eq!(
+            pst.evaluate(&board(fen)),
+            -pst.evaluate(&board(&mirrored)),
+            "{fen} vs {mirrored}"
+        );
+    }
+}
+
+#[test]
+fn pst_game_phase() {
+    let start = PstScore::new(&Board::initial());
+    assert_eq!(start.phase, MAX_PHASE);
+    // Pawns and kings only is as endgame as it gets
+    let endgame = PstScore::new(&board("8/4kp2/8/8/8/8/3PK3/8 w - - 0 1"));
+    assert_eq!(endgame.phase, 0);
+    assert_eq!(endgame.taper(), endgame.eg as i64);
+    // A queen each is a third of the way there
+    let queens = PstScore::new(&board("3qk3/8/8/8/8/8/8/3QK3 w - - 0 1"));
+    assert_eq!(queens.phase, 8);
+    let tapered = (queens.mg * 8 + queens.eg * 16) / MAX_PHASE;
+    assert_eq!(queens.taper(), tapered as i64);
+    // Extra queens don't make it any more of a middlegame than it already was
+    let promoted = PstScore::new(&board("QQQQk3/8/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1"));
+    assert!(promoted.phase > MAX_PHASE);
+    assert_eq!(promoted.taper(), promoted.mg as i64);
+}
+
+#[test]
+fn pst_incremental_updates_match_along_games() {
+    for game_no in 1..=100 {
+        let moves = read_to_string(format!("games/game_{game_no}.pgn")).unwrap();
+        let mut pst = PieceSquareTables::new();
+        let mut board = Board::initial();
+        let mut plies = 0;
+        for move_str in moves.split_whitespace() {
+            let mv = board
+                .is_legal(&AlgebraicMove::parse(move_str).unwrap())
+                .unwrap();
+            let next = board.apply(&mv);
+            // Never popped, so every position is built on top of the previous one
+            pst.make_move(&board, &next);
+            plies += 1;
+            assert_eq!(
+                pst.evaluate(&next),
+                PstScore::new(&next).taper(),
+                "game {game_no}: {move_str}"
+            );
+            board = next;
+        }
+        for _ in 0..plies {
+            pst.unmake_move();
+        }
+        assert_eq!(pst.evaluate(&board), PstScore::new(&board).taper());
+    }
+}
+
+fn squares(names: &[&str]) -> Bitboard {
+    let mut bb = Bitboard::empty();
+    for name in names {
+        let square = moves::Square::parse(name).unwrap();
+        bb |= Bitboard::at(square.x, square.y);
+    }
+    bb
+}
+
+fn white_pawns(fen: &str) -> PawnStructure {
+    let board = board(fen);
+    let white = board[Piece::Pawn] & board[Color::White];
+    let black = board[Piece::Pawn] & board[Color::Black];
+    PawnStructure::new(white, black, Color::White)
+}
+
+#[test]
+fn pawn_structure_terms() {
+    // Doubled and isolated, and only the front one can be passed
+    let pawns = white_pawns("4k3/8/8/8/2P5/2P5/8/4K3 w - - 0 1");
+    assert!(pawns.doubled == squares(&["c3"]));
+    assert!(pawns.isolated == squares(&["c3", "c4"]));
+    assert!(pawns.passed == squares(&["c4"]));
+    assert!(pawns.connected.is_empty());
+
+    // e3 can't be defended from d4, and f5 stops it from moving up
+    let pawns = white_pawns("4k3/8/8/5p2/3P4/4P3/8/4K3 w - - 0 1");
+    assert!(pawns.backward == squares(&["e3"]));
+    assert!(pawns.connected == squares(&["d4"]));
+    assert!(pawns.passed == squares(&["d4"]));
+    assert!(pawns.isolated.is_empty());
+    // Without the f pawn there's nothing wrong with it
+    let pawns = white_pawns("4k3/8/8/8/3P4/4P3/8/4K3 w - - 0 1");
+    assert!(pawns.backward.is_empty());
+
+    // Side by side counts as connected, and passed pawns are worth more further up
+    let pawns = white_pawns("4k3/8/3PP3/8/8/8/p7/4K3 w - - 0 1");
+    assert!(pawns.connected == squares(&["d6", "e6"]));
+    let far = pawns.score(Color::White);
+    let pawns = white_pawns("4k3/8/8/8/8/3PP3/p7/4K3 w - - 0 1");
+    assert!(far > pawns.score(Color::White));
+}
+
+#[test]
+fn pawn_structure_is_symmetric() {
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "4k3/8/8/5p2/3P4/4P3/8/4K3 w - - 0 1",
+        "4k3/pp6/8/2P5/2P5/8/7p/4K3 b - - 0 1",
+    ];
+    assert_eq!(pawn_score(&board(fens[0])), 0);
+    for fen in fens {
+        let mirrored = mirror(fen);
+        assert_eq!(
+            pawn_score(&board(fen)),
+            -pawn_score(&board(&mirrored)),
+            "{fen}"
+        );
+    }
+}
+
+#[test]
+fn pawn_hash_table_along_games() {
+    let mut pawns = Pawns::new();
+    for game_no in 1..=100 {
+        let moves = read_to_string(format!("games/game_{game_no}.pgn")).unwrap();
+        let mut board = Board::initial();
+        for move_str in moves.split_whitespace() {
+            let mv = board
+                .is_legal(&AlgebraicMove::parse(move_str).unwrap())
+                .unwrap();
+            board = board.apply(&mv);
+            assert_eq!(pawns.evaluate(&board), pawn_score(&board), "game {game_no}");
+        }
+    }
+    // Most moves aren't pawn moves, and all games start the same way
+    assert!(pawns.table.hits > pawns.table.misses);
+}
+
+#[test]
+fn evaluators_add_up() {
+    let board = board("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let mut pst = eval::by_name("pst").unwrap();
+    let mut pawns = eval::by_name("pawns").unwrap();
+    let mut both = eval::by_name("pst+pawns").unwrap();
+    let sum = pst.evaluate(&board) + pawns.evaluate(&board);
+    assert_eq!(both.evaluate(&board), sum);
+    assert!(eval::by_name("pst+nonsense").is_none());
+}
+
+#[test]
+fn mobility_counts_safe_squares() {
+    // A knight in the corner has two squares, in the middle eight
+    let corner = board("4k3/8/8/8/8/8/8/N3K3 w - - 0 1");
+    let centre = board("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+    assert_eq!(
+        mobility(&corner, Color::White) * 4,
+        mobility(&centre, Color::White)
+    );
+    // Squares covered by enemy pawns don't count, squares with enemy pieces on them do
+    let pawns = board("4k3/8/8/8/3N2p1/8/8/4K3 w - - 0 1");
+    assert_eq!(
+        mobility(&pawns, Color::White),
+        mobility(&centre, Color::White) - 4
+    );
+    let knights = board("4k3/8/2n5/8/3N4/8/8/4K3 w - - 0 1");
+    assert_eq!(
+        mobility(&knights, Color::White),
+        mobility(&centre, Color::White)
+    );
+    let mut mobility = eval::by_name("mobility").unwrap();
+    assert_eq!(mobility.evaluate(&Board::initial()), 0);
+}
+
+#[test]
+fn king_safety_terms() {
+    // One attacker doesn't count, two do, and the queen more than the knight
+    let queen = board("6k1/5ppp/8/8/8/8/6Q1/6K1 w - - 0 1");
+    assert_eq!(king_attacks(&queen, Color::Black), 0);
+    let both = board("6k1/5ppp/8/4N3/8/8/6Q1/6K1 w - - 0 1");
+    assert!(king_attacks(&both, Color::Black) < 0);
+    let knights = board("6k1/5ppp/8/4N3/4N3/8/8/6K1 w - - 0 1");
+    assert!(king_attacks(&both, Color::Black) < king_attacks(&knights, Color::Black));
+
+    // Pawns in front of the king are worth having, as long as there is a queen to fear
+    let sheltered = board("3qk3/8/8/8/8/8/5PPP/6K1 w - - 0 1");
+    assert_eq!(king_shelter(&sheltered, Color::White), 0);
+    let pushed = board("3qk3/8/8/8/8/6P1/5P1P/6K1 w - - 0 1");
+    assert_eq!(king_shelter(&pushed, Color::White), 0);
+    let half_open = board("3qk3/8/6p1/8/8/8/5P1P/6K1 w - - 0 1");
+    assert_eq!(king_shelter(&half_open, Color::White), -30);
+    let open = board("3qk3/8/8/8/8/8/5P1P/6K1 w - - 0 1");
+    assert_eq!(king_shelter(&open, Color::White), -40);
+    let no_queen = board("4k3/8/8/8/8/8/5P1P/6K1 w - - 0 1");
+    assert_eq!(king_shelter(&no_queen, Color::White), 0);
+
+    // Each half can be measured on its own
+    let mut king = eval::by_name("king").unwrap();
+    let mut halves = eval::by_name("king-attacks+king-shelter").unwrap();
+    for fen in [
+        "6k1/5ppp/8/4N3/8/8/6Q1/6K1 w - - 0 1",
+        "3qk3/8/8/8/8/8/5P1P/6K1 w - - 0 1",
+    ] {
+        assert_eq!(
+            king.evaluate(&board(fen)),
+            halves.evaluate(&board(fen)),
+            "{fen}"
+        );
+        let mirrored = mirror(fen);
+        assert_eq!(
+            king.evaluate(&board(fen)),
+            -king.evaluate(&board(&mirrored))
+        );
+    }
+}
+
+#[test]
+fn weighted_terms_and_traces() {
+    let board = board("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let mut pawns = eval::by_name("pawns").unwrap();
+    let mut doubled = eval::by_name("pawns:200").unwrap();
+    let mut gone = eval::by_name("pst+pawns:0").unwrap();
+    let mut pst = eval::by_name("pst").unwrap();
+    assert_eq!(doubled.evaluate(&board), 2 * pawns.evaluate(&board));
+    assert_eq!(gone.evaluate(&board), pst.evaluate(&board));
+    assert!(eval::by_name("pawns:lots").is_none());
+
+    // Every term's sides come out to its total, and the totals to the whole evaluation
+    let mut everything = eval::by_name(&eval::EVALUATORS.join("+")).unwrap();
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "3qk3/8/6p1/8/8/8/5P1P/6K1 w - - 0 1",
+    ] {
+        let board = self::board(fen);
+        let trace = everything.trace(&board);
+        assert_eq!(trace.0.len(), eval::EVALUATORS.len());
+        assert_eq!(trace.total(), everything.evaluate(&board), "{fen}");
+        for line in trace.0.iter() {
+            let (white, black) = line.sides.unwrap();
+            // Tapering rounds each side on its own
+            let slack = if line.name == "pst" { 1 } else { 0 };
+            assert!(
+                (white - black - line.total).abs() <= slack,
+                "{fen}: {}",
+                line.name
+            );
+        }
+    }
+    let start = everything.trace(&Board::initial()).to_string();
+    assert!(start
+        .lines()
+        .any(|line| line.trim().starts_with("material")));
+}
@@ -0,0 +1,84 @@
+use std::fs::read_to_string;
+
+use board::Board;
+use chess_for_crabs::*;
+use moves::AlgebraicMove;
+
+fn play(board: Board, moves: &str) -> Board {
+    let mut board = board;
+    for move_str in moves.split_whitespace() {
+        let alg = AlgebraicMove::parse(move_str).unwrap();
+        board = board.apply(&board.is_legal(&alg).unwrap());
+    }
+    board
+}
+
+#[test]
+fn fen_round_trips() {
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+        "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+        "8/5k2/8/8/8/8/3K4/8 b - - 99 143",
+        "8/5k2/8/8/8/8/3K4/8 w - - 300 1000",
+    ];
+    for fen in fens {
+        let game = fen::parse(fen).unwrap();
+        assert_eq!(game.board.fen().to_string(), fen);
+    }
+}
+
+#[test]
+fn fen_round_trips_along_games() {
+    for game_no in 1..=100 {
+        let moves = read_to_string(format!("games/game_{game_no}.pgn")).unwrap();
+        let mut board = Board::initial();
+        for move_str in moves.split_whitespace() {
+            let alg = AlgebraicMove::parse(move_str).unwrap();
+            board = board.apply(&board.is_legal(&alg).unwrap());
+            let fen = board.fen().to_string();
+            assert!(fen::parse(&fen).unwrap().board == board, "{fen}");
+        }
+    }
+}
+
+#[test]
+fn move_counters() {
+    let board = play(Board::initial(), "e4");
+    assert_eq!(
+        board.fen().to_string(),
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+    );
+    // Only Black's moves advance the full move number
+    let board = play(board, "e5 Nf3 Nc6 Bb5");
+    assert_eq!((board.half_moves, board.full_moves), (3, 3));
+    // Captures reset the clock
+    let board = play(board, "a6 Bxc6");
+    assert_eq!((board.half_moves, board.full_moves), (0, 4));
+    let board = play(board, "Nf6");
+    assert_eq!((board.half_moves, board.full_moves), (1, 5));
+    // Castling just ticks the clock
+    let board = play(board, "O-O");
+    assert_eq!((board.half_moves, board.full_moves), (2, 5));
+    let board = play(board, "dxc6");
+    assert_eq!((board.half_moves, board.full_moves), (0, 6));
+}
+
+#[test]
+fn move_counters_in_endgames() {
+    // Pawn moves and promotions reset the clock, king shuffles don't
+    let board = fen::parse("8/4P3/8/8/8/k7/8/K7 w - - 12 60").unwrap().board;
+    let shuffled = play(board, "Kb1 Kb3 Ka1 Ka3");
+    assert_eq!((shuffled.half_moves, shuffled.full_moves), (16, 62));
+    let promoted = play(board, "e8=Q");
+    assert_eq!((promoted.half_moves, promoted.full_moves), (0, 60));
+    // The clock doesn't wrap around in very long games
+    let board = fen::parse("8/8/8/8/8/k7/8/K7 w - - 65535 2000")
+        .unwrap()
+        .board;
+    assert_eq!(play(board, "Kb1").half_moves, u16::MAX);
+}
@@ -0,0 +1,208 @@
+use std::fs::read_to_string;
+
+use board::Board;
+use chess_for_crabs::*;
+use game::Game;
+use moves::{AlgebraicMove, CoordinateMove, Move};
+use piece::Piece;
+
+// The slow and obviously correct way of doing it
+fn slow_legal_moves(board: &Board) -> Vec<Move> {
+    let mut moves = Vec::new();
+    board.pre_legal_moves(&mut moves);
+    moves.retain(|mv| {
+        let castles = !matches!(mv, Move::Simple(_));
+        !board.apply(mv).in_check(board.player) && (!castles || !board.in_check(board.player))
+    });
+    moves
+}
+
+fn check_legal_moves(board: &Board) {
+    let mut fast = Vec::new();
+    board.legal_moves(&mut fast);
+    let slow = slow_legal_moves(board);
+    for mv in fast.iter() {
+        assert!(slow.contains(mv), "{mv:?} is illegal in {}", board.fen());
+    }
+    for mv in slow.iter() {
+        assert!(fast.contains(mv), "{mv:?} is missing in {}", board.fen());
+    }
+    assert_eq!(fast.len(), slow.len(), "Duplicate moves in {}", board.fen());
+}
+
+#[test]
+fn legal_moves_along_games() {
+    for game_no in 1..=100 {
+        let moves = read_to_string(format!("games/game_{game_no}.pgn")).unwrap();
+        let mut game = Game::new();
+        for move_str in moves.split_whitespace() {
+            check_legal_moves(&game.board);
+            let alg = AlgebraicMove::parse(move_str).unwrap();
+            let mv = game.board.is_legal(&alg).unwrap();
+            game.make_move(&alg, &mv);
+        }
+        check_legal_moves(&game.board);
+    }
+}
+
+#[test]
+fn algebraic_moves_round_trip_along_games() {
+    for game_no in 1..=100 {
+        let moves = read_to_string(format!("games/game_{game_no}.pgn")).unwrap();
+        let mut board = Board::initial();
+        for move_str in moves.split_whitespace() {
+            let parsed = AlgebraicMove::parse(move_str).unwrap();
+            let mv = board.is_legal(&parsed).unwrap();
+            let alg = board.to_algebraic(mv).unwrap();
+            let san = alg.to_string();
+            assert_eq!(board.is_legal(&alg).unwrap(), mv, "game {game_no}: {san}");
+            // The corpus sometimes gives the whole square (Rd1d2) where the rank is enough
+            let over_disambiguated = match (parsed, alg) {
+                (AlgebraicMove::Simple(parsed), AlgebraicMove::Simple(alg)) => {
+                    matches!(parsed.disambiguate, (Some(_), Some(_)))
+                        && matches!(alg.disambiguate, (None, Some(_)))
+                }
+                _ => false,
+            };
+            if !over_disambiguated {
+                assert_eq!(san, move_str, "game {game_no}");
+            }
+            board = board.apply(&mv);
+        }
+    }
+}
+
+#[test]
+fn coordinate_moves_round_trip_along_games() {
+    for game_no in 1..=100 {
+        let moves = read_to_string(format!("games/game_{game_no}.pgn")).unwrap();
+        let mut board = Board::initial();
+        for move_str in moves.split_whitespace() {
+            let mv = board
+                .is_legal(&AlgebraicMove::parse(move_str).unwrap())
+                .unwrap();
+            let coords = board.to_coordinate(mv).to_string();
+            let parsed = CoordinateMove::parse(&coords).unwrap();
+            // Moves from the generator and from SAN can look different, but must do the same
+            let from_coords = board.from_coordinate(&parsed).unwrap();
+            assert!(
+                board.apply(&from_coords) == board.apply(&mv),
+                "game {game_no}: {coords}"
+            );
+            board = board.apply(&mv);
+        }
+    }
+}
+
+#[test]
+fn coordinate_moves() {
+    let board = fen::parse("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1")
+        .unwrap()
+        .board;
+    let play = |coords: &str| {
+        let mv = board.from_coordinate(&CoordinateMove::parse(coords).unwrap())?;
+        Some(board.to_algebraic(mv).unwrap().to_string())
+    };
+    assert_eq!(play("e1g1").as_deref(), Some("O-O"));
+    assert_eq!(play("e1c1").as_deref(), Some("O-O-O"));
+    assert_eq!(play("b7a8q").as_deref(), Some("bxa8=Q+"));
+    assert_eq!(play("b7b8n").as_deref(), Some("b8=N"));
+    assert_eq!(play("a1a8").as_deref(), Some("Rxa8+"));
+    // Promotions have to say what to
+    assert_eq!(play("b7b8"), None);
+    assert_eq!(play("e1e3"), None);
+
+    for bad in ["e2", "e2e9", "i2i4", "e7e8k", "e7e8Q", "e2e4+", "Nf3"] {
+        assert!(CoordinateMove::parse(bad).is_none(), "{bad}");
+    }
+    let mv = CoordinateMove::parse("e7e8n").unwrap();
+    assert_eq!(mv.promotion, Some(Piece::Knight));
+    assert_eq!(mv.to_string(), "e7e8n");
+}
+
+// Everything that takes a piece or promotes, out of all the moves
+fn check_captures(board: &Board) {
+    let mut all = Vec::new();
+    board.pre_legal_moves(&mut all);
+    let them = board[board.player.opponent()];
+    let our_pawns = board[Piece::Pawn] & board[board.player];
+    all.retain(|mv| match mv {
+        Move::Simple(mv) => {
+            let promotes = (mv.delete & our_pawns).is_populated() && mv.piece != Piece::Pawn;
+            (mv.delete & them).is_populated() || promotes
+        }
+        _ => false,
+    });
+    let mut captures = Vec::new();
+    board.pre_legal_captures(&mut captures);
+    assert_eq!(captures.len(), all.len(), "{}", board.fen());
+    for mv in captures.iter() {
+        assert!(all.contains(mv), "{mv:?} in {}", board.fen());
+    }
+}
+
+#[test]
+fn captures_along_games() {
+    for game_no in 1..=100 {
+        let moves = read_to_string(format!("games/game_{game_no}.pgn")).unwrap();
+        let mut board = Board::initial();
+        for move_str in moves.split_whitespace() {
+            check_captures(&board);
+            let mv = board
+                .is_legal(&AlgebraicMove::parse(move_str).unwrap())
+                .unwrap();
+            board = board.apply(&mv);
+        }
+    }
+    for fen in [
+        "8/8/8/K1pP3r/8/8/8/7k w - c6 0 1",
+        "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/p7/1N2K3 b - - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ] {
+        check_captures(&fen::parse(fen).unwrap().board);
+    }
+}
+
+#[test]
+fn legal_moves_tricky_positions() {
+    let fens = [
+        // En passant would uncover a check along the rank
+        "8/8/8/K1pP3r/8/8/8/7k w - c6 0 1",
+        // Double check
+        "4k3/8/8/8/8/5n2/8/r3K3 w - - 0 1",
+        // Pinned pieces, some of which can slide along the pin
+        "4k3/4r3/8/b7/8/2N5/3B4/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/4Q3/r3K2q w - - 0 1",
+        // Castling out of, through and into check
+        "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+        "4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1",
+        "4k3/8/8/8/8/8/5r2/R3K2R w KQ - 0 1",
+        "4k3/8/8/8/8/8/6r1/R3K2R w KQ - 0 1",
+        // Promotions, with and without captures
+        "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+        // Kiwipete
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ];
+    for fen in fens {
+        let board = fen::parse(fen).unwrap().board;
+        check_legal_moves(&board);
+    }
+}
+
+#[test]
+fn no_legal_moves_when_mated_or_stalemated() {
+    // Fool's mate
+    let mated =
+        fen::parse("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+    let stalemated = fen::parse("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    for game in [&mated, &stalemated] {
+        let mut moves = Vec::new();
+        game.board.legal_moves(&mut moves);
+        assert!(moves.is_empty(), "{}", game.board.fen());
+    }
+    assert!(mated.board.is_checkmate() && !mated.board.is_stalemate());
+    assert!(stalemated.board.is_stalemate() && !stalemated.board.is_checkmate());
+    let initial = board::Board::initial();
+    assert!(!initial.is_checkmate() && !initial.is_stalemate());
+}
@@ -0,0 +1,128 @@
+use chess_for_crabs::*;
+
+// Known node counts for depth 1, 2, ... from the usual references (chessprogramming.org and
+// friends). Depths are kept small enough for debug builds; run `perft` from the CLI on a
+// release build for the deeper ones.
+fn check(fen: &str, expected: &[u64]) {
+    let board = fen::parse(fen).unwrap().board;
+    for (depth, nodes) in expected.iter().enumerate() {
+        let depth = depth as u64 + 1;
+        assert_eq!(board.perft(depth), *nodes, "perft {depth} of {fen}");
+    }
+}
+
+#[test]
+fn perft_start_position() {
+    let board = board::Board::initial();
+    assert_eq!(board.perft(0), 1);
+    check(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[20, 400, 8902, 197281],
+    );
+}
+
+#[test]
+fn perft_kiwipete() {
+    check(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        &[48, 2039, 97862],
+    );
+}
+
+#[test]
+fn perft_reference_positions() {
+    // Lots of en passant and rook endgame tactics
+    check(
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        &[14, 191, 2812, 43238, 674624],
+    );
+    // Promotions and castling after the rook gets captured
+    check(
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        &[6, 264, 9467, 422333],
+    );
+    check(
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        &[44, 1486, 62379],
+    );
+    check(
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        &[46, 2079, 89890],
+    );
+}
+
+#[test]
+fn perft_edge_cases() {
+    // En passant that would expose the king
+    check(
+        "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+        &[15, 126, 1928, 13931, 206379],
+    );
+    check(
+        "8/5bk1/8/2Pp4/8/1K6/8/8 w - d6 0 1",
+        &[8, 104, 736, 9287, 62297],
+    );
+    check(
+        "3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1",
+        &[18, 92, 1670, 10138, 185429],
+    );
+    // Promotions, including out of and into check
+    check(
+        "2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1",
+        &[11, 133, 1442, 19174, 266199],
+    );
+    check("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", &[9, 40, 472, 2661, 38983]);
+    check("8/P1k5/K7/8/8/8/8/8 w - - 0 1", &[6, 27, 273, 1329, 18135]);
+    check("8/k1P5/8/1K6/8/8/8/8 w - - 0 1", &[10, 25, 268, 926, 10857]);
+    // Stalemate and checkmate
+    check("K1k5/8/P7/8/8/8/8/8 w - - 0 1", &[2, 6, 13, 63, 382]);
+    check(
+        "8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1",
+        &[29, 165, 5160, 31961],
+    );
+    // Castling, through and out of check
+    check(
+        "r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1",
+        &[26, 1141, 27826],
+    );
+    check(
+        "r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1",
+        &[44, 1494, 50509],
+    );
+}
+
+// Rights lost along the way, and partial rights to begin with
+#[test]
+fn perft_castling_rights() {
+    check(
+        "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+        &[26, 568, 13744, 314346, 7594526],
+    );
+    check(
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        &[6, 264, 9467, 422333],
+    );
+    check(
+        "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+        &[15, 66, 1198, 6399, 120330],
+    );
+    check(
+        "3k4/8/8/8/8/8/8/R3K3 w Q - 0 1",
+        &[16, 71, 1286, 7418, 141077],
+    );
+}
+
+#[test]
+fn divide_adds_up_to_perft() {
+    let board = fen::parse("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+        .unwrap()
+        .board;
+    let divide = board.divide(3);
+    assert_eq!(divide.len(), 48);
+    assert_eq!(divide.iter().map(|(_, n)| n).sum::<u64>(), board.perft(3));
+    for (mv, nodes) in divide {
+        assert_eq!(board.apply(&mv).perft(2), nodes);
+    }
+    assert_eq!(board.perft(0), 1);
+    assert!(board.divide(0).is_empty());
+}
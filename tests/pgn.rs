@@ -0,0 +1,272 @@
+use std::fs::read_to_string;
+
+use chess_for_crabs::*;
+use pgn::{GameResult, PgnError, PgnErrorKind};
+
+const DATABASE: &str = r#"[Event "Casual game"]
+[Site "London"]
+[Date "1851.06.21"]
+[Round "-"]
+[White "Anderssen, Adolf"]
+[Black "Kieseritzky, Lionel"]
+[Result "1-0"]
+[Annotator "Someone \"quoted\""]
+
+% This line is for some other program
+{The Immortal Game.} 1.e4 e5 2.f4 exd4?? 1-0
+"#;
+
+const ANNOTATED: &str = r#"[Event "Annotated"]
+[White "A"]
+[Black "B"]
+[Result "1/2-1/2"]
+
+{Before anything} 1. e4 $1 e5 2. Nf3 {Developing} (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6!?
+; a comment until the end of the line
+3. Bb5 a6 4. Ba4 (4. Bxc6 dxc6 $14) 4... Nf6 5. O-O 1/2-1/2
+
+[Event "From a position"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"]
+[Result "1-0"]
+
+1. O-O-O Kf7 2. Rd7+ Ke6 *
+
+[Event "Castling with zeroes"]
+[SetUp "1"]
+[FEN "r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1"]
+[Result "0-1"]
+
+1. 0-0 0-0-0 0-1
+"#;
+
+#[test]
+fn pgn_reads_the_game_corpus() {
+    for game_no in 1..=100 {
+        let moves = read_to_string(format!("games/game_{game_no}.pgn")).unwrap();
+        let games = pgn::parse(&moves).unwrap();
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert!(game.tags.is_empty());
+        assert_eq!(game.result, GameResult::Unknown);
+        assert_eq!(
+            game.mainline.moves.len(),
+            moves.split_whitespace().count(),
+            "game {game_no}"
+        );
+    }
+}
+
+#[test]
+fn pgn_tags_comments_variations_and_nags() {
+    let games = pgn::parse(ANNOTATED).unwrap();
+    assert_eq!(games.len(), 3);
+
+    let game = &games[0];
+    assert_eq!(game.tag("White"), Some("A"));
+    assert_eq!(game.tag("Round"), None);
+    assert_eq!(game.result, GameResult::Draw);
+    let line = &game.mainline;
+    assert_eq!(line.comments, vec!["Before anything"]);
+    assert_eq!(line.moves.len(), 9);
+    assert_eq!(line.moves[0].nags, vec![1]);
+    assert_eq!(line.moves[2].comments, vec!["Developing"]);
+    assert_eq!(line.moves[3].nags, vec![5]);
+
+    // 2. f4 replaces 2. Nf3, with 2... d5 nested inside it replacing 2... exf4
+    let variations = &line.moves[2].variations;
+    assert_eq!(variations.len(), 1);
+    let king_gambit = &variations[0].moves;
+    assert_eq!(king_gambit.len(), 3);
+    assert_eq!(king_gambit[0].alg.to_string(), "f4");
+    assert_eq!(king_gambit[1].variations[0].moves[0].alg.to_string(), "d5");
+    // The exchange variation starts from the position before 4. Ba4
+    let exchange = &line.moves[6].variations[0].moves;
+    assert_eq!(exchange[1].nags, vec![14]);
+
+    let board = game.to_game().board;
+    assert_eq!(
+        board.fen().to_string(),
+        "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 3 5"
+    );
+
+    let game = &games[1];
+    assert_eq!(game.tag("SetUp"), Some("1"));
+    assert_eq!(game.result, GameResult::Unknown);
+    assert_eq!(
+        game.to_game().board.fen().to_string(),
+        "8/3R4/4k3/8/8/8/8/2K5 w - - 4 3"
+    );
+
+    // Not to be confused with the result right after it
+    let game = &games[2];
+    assert_eq!(game.result, GameResult::BlackWins);
+    assert_eq!(game.mainline.moves[1].alg.to_string(), "O-O-O");
+    assert_eq!(
+        game.to_game().board.fen().to_string(),
+        "2kr4/8/8/8/8/8/8/5RK1 w - - 2 2"
+    );
+}
+
+#[test]
+fn pgn_tag_escapes_and_escaped_lines() {
+    // exd4 isn't a legal move, but everything before it was read just fine
+    let PgnError {
+        game,
+        line,
+        column,
+        kind,
+    } = pgn::parse(DATABASE).err().unwrap();
+    assert_eq!((game, line, column), (1, 11, 35));
+    assert!(matches!(kind, PgnErrorKind::IllegalMove(mv, _) if mv == "exd4??"));
+    let fixed = DATABASE.replace("exd4??", "exf4??");
+    let games = pgn::parse(&fixed).unwrap();
+    assert_eq!(games[0].tag("Annotator"), Some("Someone \"quoted\""));
+    assert_eq!(games[0].result, GameResult::WhiteWins);
+    assert_eq!(games[0].mainline.moves[3].nags, vec![4]);
+}
+
+fn error_at(pgn: &str) -> (usize, usize, usize, PgnErrorKind) {
+    let err = pgn::parse(pgn).err().unwrap();
+    (err.game, err.line, err.column, err.kind)
+}
+
+#[test]
+fn pgn_error_locations() {
+    use PgnErrorKind::*;
+    let two_games = "[Event \"1\"]\n\n1. e4 e5 1-0\n\n[Event \"2\"]\n\n1. d4 d5\n2. Nf3 Ke3 *\n";
+    let (game, line, column, kind) = error_at(two_games);
+    assert_eq!((game, line, column), (2, 8, 8));
+    assert!(matches!(kind, IllegalMove(mv, _) if mv == "Ke3"));
+
+    assert_eq!(
+        error_at("1. e4 Zf6"),
+        (1, 1, 7, InvalidMove("Zf6".to_string()))
+    );
+    assert_eq!(
+        error_at("1. e4 {never closed\n"),
+        (1, 1, 7, UnterminatedComment)
+    );
+    assert_eq!(
+        error_at("1. e4 e5 (1... c5 2. Nf3\n"),
+        (1, 2, 1, UnterminatedVariation)
+    );
+    // The result ends the first game, so the stray parenthesis is in the second one
+    assert_eq!(
+        error_at("1. e4 e5 (1... c5) 1-0 2. Nf3)"),
+        (2, 1, 30, UnbalancedParenthesis)
+    );
+    assert_eq!(error_at("1. e4 e5 )"), (1, 1, 10, UnbalancedParenthesis));
+    assert_eq!(error_at("(1. e4)"), (1, 1, 1, NothingToAnnotate));
+    assert_eq!(error_at("$3 1. e4"), (1, 1, 1, NothingToAnnotate));
+    assert_eq!(error_at("[Event \"x]\n1. e4"), (1, 1, 1, MalformedTag));
+    assert_eq!(error_at("1. e4 & e5"), (1, 1, 7, UnexpectedCharacter('&')));
+    assert_eq!(
+        error_at("[FEN \"not a fen\"]\n1. e4"),
+        (1, 1, 1, InvalidFen("not a fen".to_string()))
+    );
+}
+
+#[test]
+fn pgn_empty_database() {
+    assert!(pgn::parse("").unwrap().is_empty());
+    assert!(pgn::parse("\n  \n").unwrap().is_empty());
+}
+
+fn play(fen: &str, moves: &str) -> game::Game {
+    let mut game = fen::parse(fen).unwrap();
+    for move_str in moves.split_whitespace() {
+        let alg = moves::AlgebraicMove::parse(move_str).unwrap();
+        let mv = game.board.is_legal(&alg).unwrap();
+        game.make_move(&alg, &mv);
+    }
+    game
+}
+
+fn movetext(game: &game::Game) -> String {
+    let pgn = pgn::write(game, &[], game.result());
+    pgn.split("\n\n").nth(1).unwrap().trim_end().to_string()
+}
+
+#[test]
+fn pgn_writes_the_seven_tag_roster() {
+    let game = play(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "f3 e5 g4 Qh4",
+    );
+    let tags = [
+        ("White", "Crab"),
+        ("Annotator", "A \"quoted\" name"),
+        ("Event", "Test"),
+        ("Result", "1-0"),
+    ];
+    let pgn = pgn::write(&game, &tags, game.result());
+    let expected = r#"[Event "Test"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "Crab"]
+[Black "?"]
+[Result "0-1"]
+[Annotator "A \"quoted\" name"]
+
+1. f3 e5 2. g4 Qh4# 0-1
+"#;
+    assert_eq!(pgn, expected);
+    let read = pgn::parse(&pgn).unwrap();
+    assert_eq!(read[0].tag("Annotator"), Some("A \"quoted\" name"));
+}
+
+#[test]
+fn pgn_writes_setup_for_other_start_positions() {
+    let fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 5 20";
+    let game = play(fen, "O-O-O Ra2 Rd1+");
+    let pgn = pgn::write(&game, &[], pgn::GameResult::Unknown);
+    assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 5 20\"]\n"));
+    assert!(pgn.ends_with("\n20... O-O-O 21. Ra2 Rd1+ *\n"));
+    let read = pgn::parse(&pgn).unwrap();
+    assert!(read[0].to_game().board == game.board);
+}
+
+#[test]
+fn pgn_writes_disambiguated_san() {
+    // Knights on b1 and f3 can both go to d2
+    let game = play("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1", "Nbd2");
+    assert_eq!(movetext(&game), "1. Nbd2 *");
+    // Rooks on the same file
+    let game = play("4k3/R7/8/8/8/8/8/R3K3 w - - 0 1", "R1a3");
+    assert_eq!(movetext(&game), "1. R1a3 *");
+    // Three queens, so neither file nor rank is enough for the one on h4
+    let game = play("2k5/8/8/8/4Q2Q/8/8/K6Q w - - 0 1", "Qh4e1");
+    assert_eq!(movetext(&game), "1. Qh4e1 *");
+    // Pawn captures always get their file, and promotions can give check
+    let game = play("3r1k2/4P3/8/8/8/8/8/4K3 w - - 0 1", "exd8=Q+");
+    assert_eq!(movetext(&game), "1. exd8=Q+ *");
+    let game = play("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "O-O+");
+    assert_eq!(movetext(&game), "1. O-O+ *");
+}
+
+#[test]
+fn pgn_round_trips_the_game_corpus() {
+    for game_no in 1..=100 {
+        let moves = read_to_string(format!("games/game_{game_no}.pgn")).unwrap();
+        let game = play(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &moves,
+        );
+        let pgn = pgn::write(&game, &[], game.result());
+        for line in pgn.lines() {
+            assert!(line.len() <= 80, "game {game_no}: {line}");
+        }
+        let read = pgn::parse(&pgn).unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].result, game.result());
+        let moves: Vec<_> = read[0].mainline.moves.iter().map(|mv| mv.mv).collect();
+        let mut board = game.start;
+        for (alg, mv) in game.log.moves.iter().zip(&moves) {
+            assert_eq!(board.is_legal(alg).unwrap(), *mv, "game {game_no}");
+            board = board.apply(mv);
+        }
+        assert_eq!(moves.len(), game.log.moves.len());
+    }
+}
@@ -9,7 +9,7 @@ use moves::AlgebraicMove;
 enum Error {
     IOError(std::io::Error),
     ParseError(String),
-    IllegalMove(Game, AlgebraicMove, IllegalMove),
+    IllegalMove(Box<Game>, AlgebraicMove, IllegalMove),
 }
 
 impl Debug for Error {
@@ -43,7 +43,7 @@ fn test_position(game_no: usize) -> Result<(), Error> {
         let alg = AlgebraicMove::parse(&move_str).ok_or(Error::ParseError(move_str.to_string()))?;
         let mv = match game.board.is_legal(&alg) {
             Ok(mv) => mv,
-            Err(illegal) => return Err(Error::IllegalMove(game, alg, illegal)),
+            Err(illegal) => return Err(Error::IllegalMove(Box::new(game), alg, illegal)),
         };
         game.make_move(&alg, &mv)
     }
@@ -0,0 +1,350 @@
+use chess_for_crabs::*;
+use eval::MaterialCount;
+use ordering::{MoveOrdering, MovePicker};
+use pst::PieceSquareTables;
+use search::{Score, SearchLimits, SearchOptions, IDAB, MATE};
+
+#[test]
+fn search_finds_hanging_queen() {
+    let game = fen::parse("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+    let mut search = IDAB::new(MaterialCount());
+    let result = search.search(game.board, 2);
+    let best = result.best_move.unwrap();
+    let alg = game.board.to_algebraic(best).unwrap();
+    assert_eq!(alg.to_string(), "Rxd5");
+    assert_eq!(result.score, Score(500));
+}
+
+#[test]
+fn search_pv_is_playable() {
+    let game = game::Game::new();
+    let mut search = IDAB::new(MaterialCount());
+    let depth = 4;
+    let result = search.search(game.board, depth);
+    assert_eq!(result.pv.len(), depth as usize);
+    assert_eq!(
+        result.score,
+        Score(search.evaluate(game.board, game.board.player, depth, i64::MIN, i64::MAX))
+    );
+
+    let mut board = game.board;
+    for mv in result.pv.iter() {
+        let alg = board.to_algebraic(*mv).unwrap();
+        assert!(board.is_legal(&alg).is_ok());
+        board = board.apply(mv)
+    }
+}
+
+#[test]
+fn iterate_deepens_up_to_the_depth_limit() {
+    let game = game::Game::new();
+    let mut search = IDAB::new(MaterialCount());
+    let mut depths = Vec::new();
+    let result = search.iterate(game.board, &SearchLimits::depth(4), |result, _| {
+        depths.push(result.depth)
+    });
+    assert_eq!(depths, vec![1, 2, 3, 4]);
+    assert_eq!(result.depth, 4);
+    assert_eq!(result.score, search.search(game.board, 4).score);
+}
+
+#[test]
+fn iterate_falls_back_to_last_completed_iteration() {
+    let game = game::Game::new();
+    let mut search = IDAB::new(MaterialCount());
+    let limits = SearchLimits {
+        nodes: Some(5000),
+        ..Default::default()
+    };
+    let result = search.iterate(game.board, &limits, |_, _| ());
+    assert!(result.nodes <= 5000);
+    assert!(result.best_move.is_some());
+    // The aborted iteration must not leak into the result
+    let completed = search.search(game.board, result.depth);
+    assert_eq!(result.score, completed.score);
+    assert_eq!(result.best_move, completed.best_move);
+}
+
+#[test]
+fn iterate_stops_anywhere() {
+    // Running out of nodes the first time the search gets a ply deeper than ever before
+    // has to work too, so try every limit until well past that
+    let board = fen::parse("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+        .unwrap()
+        .board;
+    // Nothing in here lets the TT change a score, as long as everything in it is right
+    let mut search = IDAB::with_tt_size(MaterialCount(), 1);
+    search.options = SearchOptions::exact();
+    let mut fresh = IDAB::with_tt_size(MaterialCount(), 0);
+    fresh.options = SearchOptions::exact();
+    let mut scores = Vec::new();
+    for nodes in 1..=1000 {
+        let limits = SearchLimits {
+            nodes: Some(nodes),
+            ..Default::default()
+        };
+        search.tt.clear();
+        let result = search.iterate(board, &limits, |_, _| ());
+        // Same TT as the aborted iteration left behind, which had better not have
+        // anything from it in there that isn't true
+        let next = search.search(board, result.depth + 1);
+        while scores.len() <= result.depth as usize + 1 {
+            scores.push(fresh.search(board, scores.len() as u64).score);
+        }
+        assert_eq!(result.score, scores[result.depth as usize], "{nodes} nodes");
+        assert_eq!(
+            next.score,
+            scores[result.depth as usize + 1],
+            "{nodes} nodes"
+        );
+    }
+}
+
+#[test]
+fn iterate_completes_depth_one_even_when_stopped() {
+    let game = game::Game::new();
+    let mut search = IDAB::new(MaterialCount());
+    search
+        .stop_handle()
+        .store(true, std::sync::atomic::Ordering::Relaxed);
+    let result = search.iterate(game.board, &SearchLimits::default(), |_, _| ());
+    assert_eq!(result.depth, 1);
+    assert!(result.best_move.is_some());
+}
+
+#[test]
+fn transposition_table_preserves_scores() {
+    let fens = [
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+    ];
+    for fen in fens {
+        let board = fen::parse(fen).unwrap().board;
+        let mut with_tt = IDAB::new(MaterialCount());
+        let mut without_tt = IDAB::with_tt_size(MaterialCount(), 0);
+        let depth = 4;
+        let fast = with_tt.search(board, depth);
+        let slow = without_tt.search(board, depth);
+        assert_eq!(fast.score, slow.score, "{fen}");
+        assert!(fast.nodes <= slow.nodes, "{fen}");
+    }
+}
+
+#[test]
+fn move_ordering_preserves_scores() {
+    let fens = [
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ];
+    for fen in fens {
+        let board = fen::parse(fen).unwrap().board;
+        // Nothing that depends on the order moves come in, and no captures past the horizon
+        // either, or the unordered search takes forever
+        let mut ordered = IDAB::with_tt_size(MaterialCount(), 0);
+        ordered.options = SearchOptions::exact();
+        let mut unordered = IDAB::with_tt_size(MaterialCount(), 0);
+        unordered.options = SearchOptions {
+            move_ordering: false,
+            ..SearchOptions::exact()
+        };
+        let depth = 4;
+        let fast = ordered.search(board, depth);
+        let slow = unordered.search(board, depth);
+        assert_eq!(fast.score, slow.score, "{fen}");
+        assert!(fast.nodes < slow.nodes, "{fen}");
+    }
+}
+
+#[test]
+fn quiescence_sees_the_recapture() {
+    // Qxd5 looks like a free pawn, unless you look one move further
+    let board = fen::parse("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1")
+        .unwrap()
+        .board;
+    let mut naive = IDAB::new(MaterialCount());
+    naive.options = SearchOptions {
+        quiescence: false,
+        ..Default::default()
+    };
+    let result = naive.search(board, 1);
+    assert_eq!(
+        board
+            .to_algebraic(result.best_move.unwrap())
+            .unwrap()
+            .to_string(),
+        "Qxd5"
+    );
+    assert_eq!(result.score, Score(800));
+
+    let mut search = IDAB::new(MaterialCount());
+    let result = search.search(board, 1);
+    assert_ne!(
+        board
+            .to_algebraic(result.best_move.unwrap())
+            .unwrap()
+            .to_string(),
+        "Qxd5"
+    );
+    assert_eq!(result.score, Score(700));
+    // And the PV stays as long as the search was deep
+    assert_eq!(result.pv.len(), 1);
+
+    // Getting out of check isn't optional, so standing pat isn't either: Rxe8 is mate
+    let board = fen::parse("4r1k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - 0 1")
+        .unwrap()
+        .board;
+    let result = search.search(board, 1);
+    assert_eq!(result.score, Score(MATE - 1));
+}
+
+#[test]
+fn pruning_saves_nodes() {
+    let board = fen::parse("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+        .unwrap()
+        .board;
+    let mut exact = IDAB::with_tt_size(PieceSquareTables::new(), 0);
+    exact.options = SearchOptions {
+        quiescence: true,
+        ..SearchOptions::exact()
+    };
+    exact.search(board, 5);
+    let mut pruned = IDAB::with_tt_size(PieceSquareTables::new(), 0);
+    pruned.search(board, 5);
+    assert!(pruned.searched_positions < exact.searched_positions / 2);
+    // The tactics are still there
+    let board = fen::parse("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1")
+        .unwrap()
+        .board;
+    let result = pruned.search(board, 4);
+    let alg = board.to_algebraic(result.best_move.unwrap()).unwrap();
+    assert_eq!(alg.to_string(), "Rxd5");
+    let board = fen::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")
+        .unwrap()
+        .board;
+    let result = pruned.search(board, 4);
+    assert_eq!(result.score, Score(MATE - 1));
+}
+
+#[test]
+fn no_null_moves_with_only_pawns() {
+    // Passing would be the best move here if it were allowed, so the search can't pretend
+    // it is: with only pawns left, turning null moves off changes nothing
+    let board = fen::parse("8/8/3k4/3p4/3P4/3K4/8/8 w - - 0 1")
+        .unwrap()
+        .board;
+    let mut with = IDAB::with_tt_size(MaterialCount(), 0);
+    let with_result = with.search(board, 8);
+    let mut without = IDAB::with_tt_size(MaterialCount(), 0);
+    without.options.null_move = false;
+    let without_result = without.search(board, 8);
+    assert_eq!(with.searched_positions, without.searched_positions);
+    assert_eq!(with_result.score, without_result.score);
+}
+
+#[test]
+fn move_picker_order() {
+    let board = fen::parse("4k3/8/3r4/2npq3/3P4/1B6/8/R6K w - - 0 1")
+        .unwrap()
+        .board;
+    let mut moves = Vec::new();
+    board.legal_moves(&mut moves);
+    let find = |san: &str| {
+        moves
+            .iter()
+            .copied()
+            .find(|mv| board.to_algebraic(*mv).unwrap().to_string() == san)
+            .unwrap()
+    };
+    // A cutoff makes a quiet move a killer for that ply, and only that ply
+    let mut ordering = MoveOrdering::new();
+    ordering.cutoff(&board, find("Ra8+"), 3, 1);
+    let order = |ply| {
+        MovePicker::new(&board, &moves, Some(find("Kg1")), &ordering, ply)
+            .map(|mv| board.to_algebraic(mv).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    // Bxd5 gives up a bishop for a pawn if the rook takes back, so it has to wait
+    let killed = order(1);
+    assert_eq!(
+        killed[..5],
+        ["Kg1", "dxe5", "dxc5", "Ra8+", "Bxd5"].map(String::from)
+    );
+    assert_eq!(killed.len(), moves.len());
+    assert_eq!(order(2)[3], "Bxd5");
+}
+
+#[test]
+fn every_promotion_is_tactical() {
+    let board = fen::parse("4k3/1P6/8/8/8/8/6P1/4K3 w - - 0 1")
+        .unwrap()
+        .board;
+    let mut moves = Vec::new();
+    board.legal_moves(&mut moves);
+    for mv in moves {
+        let san = board.to_algebraic(mv).unwrap().to_string();
+        let promotes = san.starts_with("b8");
+        assert_eq!(ordering::is_tactical(&board, &mv), promotes, "{san}");
+    }
+}
+
+#[test]
+fn search_prefers_the_fastest_mate() {
+    // Ra8# right away, but there's plenty of slower mates too
+    let board = fen::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")
+        .unwrap()
+        .board;
+    let mut search = IDAB::new(MaterialCount());
+    let result = search.search(board, 4);
+    assert_eq!(result.score, Score(MATE - 1));
+    assert_eq!(result.score.to_string(), "#1");
+    let best = board.apply(&result.best_move.unwrap());
+    assert!(best.is_checkmate());
+}
+
+#[test]
+fn search_scores_mate_for_black() {
+    // Fool's mate, Black to play Qh4#
+    let board = fen::parse("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2")
+        .unwrap()
+        .board;
+    let mut search = IDAB::new(MaterialCount());
+    let result = search.iterate(board, &SearchLimits::depth(3), |_, _| ());
+    assert_eq!(result.score, Score(-MATE + 1));
+    assert_eq!(result.score.to_string(), "-#1");
+}
+
+#[test]
+fn search_scores_terminal_positions() {
+    let mut search = IDAB::new(MaterialCount());
+    // Stalemate is a draw, however much material is on the board
+    let stalemate = fen::parse("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap().board;
+    let result = search.search(stalemate, 3);
+    assert_eq!(result.score, Score(0));
+    assert!(result.best_move.is_none());
+    // Already mated: mate in zero
+    let mated = fen::parse("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3")
+        .unwrap()
+        .board;
+    let result = search.search(mated, 3);
+    assert_eq!(result.score, Score(-MATE));
+    assert!(result.best_move.is_none());
+}
+
+#[test]
+fn score_display() {
+    assert_eq!(Score(37).to_string(), "37");
+    assert_eq!(Score(-250).to_string(), "-250");
+    assert_eq!(Score(MATE - 1).to_string(), "#1");
+    assert_eq!(Score(MATE - 5).to_string(), "#3");
+    assert_eq!(Score(-MATE + 2).to_string(), "-#1");
+    assert_eq!(Score(-MATE + 4).to_string(), "-#2");
+    // Mated already, rather than mating
+    assert_eq!(Score(-MATE).to_string(), "-#0");
+    assert_eq!(Score(MATE).to_string(), "#0");
+    assert_eq!(Score(MATE - 4).mate_in(), Some(2));
+    assert_eq!(Score(900).mate_in(), None);
+}
@@ -0,0 +1,66 @@
+use board::Board;
+use chess_for_crabs::*;
+use moves::{AlgebraicMove, Move};
+
+fn board(fen: &str) -> Board {
+    fen::parse(fen).unwrap().board
+}
+
+fn see(fen: &str, san: &str) -> i64 {
+    let board = board(fen);
+    let mv: Move = board.is_legal(&AlgebraicMove::parse(san).unwrap()).unwrap();
+    board.see(&mv)
+}
+
+fn squares(names: &[&str]) -> bitboard::Bitboard {
+    let mut bb = bitboard::Bitboard::empty();
+    for name in names {
+        let square = moves::Square::parse(name).unwrap();
+        bb |= bitboard::Bitboard::at(square.x, square.y);
+    }
+    bb
+}
+
+#[test]
+fn attackers_to_both_colours() {
+    let board = board("4k3/8/3r4/2npq3/3P4/1B6/8/R6K w - - 0 1");
+    let d5 = squares(&["d5"]);
+    let everything = board.occupancy();
+    assert!(board.attackers_to(d5, everything) == squares(&["b3", "d6", "e5"]));
+    // The rook behind the rook joins in once the front one is gone
+    let board = self::board("3rk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1");
+    let d5 = squares(&["d5"]);
+    assert!(board.attackers_to(d5, board.occupancy()) == squares(&["d2", "d7"]));
+    let without = board.occupancy() & !squares(&["d2", "d7"]);
+    assert!(board.attackers_to(d5, without) == squares(&["d1", "d8"]));
+}
+
+#[test]
+fn static_exchange_evaluation() {
+    // Free pawn
+    assert_eq!(see("4k3/8/8/3p4/8/8/8/3RK3 w - - 0 1", "Rxd5"), 100);
+    // Defended pawn
+    assert_eq!(see("4k3/8/4p3/3p4/8/8/8/3RK3 w - - 0 1", "Rxd5"), -400);
+    // Doubled rooks win it, unless Black doubles up too
+    assert_eq!(see("4k3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "Rxd5"), 100);
+    assert_eq!(see("3rk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "Rxd5"), -400);
+    // Taking with the queen is a bad idea, and taking the queen a good one
+    assert_eq!(see("4k3/8/4p3/3n4/8/8/8/3QK3 w - - 0 1", "Qxd5"), -580);
+    assert_eq!(see("4k3/8/4p3/3q4/4P3/8/8/4K3 w - - 0 1", "exd5"), 800);
+    // Nobody has to take back if it only loses more: Qxd5 Rxd5 leaves Black a queen for a
+    // rook down, so Black would rather just be a pawn down
+    assert_eq!(see("3qk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "Rxd5"), 100);
+    // Even trades
+    assert_eq!(see("4k3/8/4p3/3n4/8/4N3/8/4K3 w - - 0 1", "Nxd5"), 0);
+    // The queen behind the bishop takes back through where it was, which gets a pawn back,
+    // unless there's a bishop behind the f pawn as well
+    assert_eq!(see("4k3/8/5p2/4p3/3B4/2Q5/8/4K3 w - - 0 1", "Bxe5"), -130);
+    assert_eq!(see("4k3/6b1/5p2/4p3/3B4/2Q5/8/4K3 w - - 0 1", "Bxe5"), -230);
+    // The king can't take back while the square is still covered
+    assert_eq!(see("8/8/8/4k3/3p4/4P3/8/3RK3 w - - 0 1", "exd4"), 100);
+    // Moving a piece to where it can be taken counts too
+    assert_eq!(see("4k3/8/4p3/8/8/8/8/3RK3 w - - 0 1", "Rd5"), -500);
+    assert_eq!(see("4k3/8/8/8/8/8/8/3RK3 w - - 0 1", "Rd5"), 0);
+    // En passant
+    assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "exd6"), 100);
+}
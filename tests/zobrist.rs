@@ -0,0 +1,60 @@
+use std::fs::read_to_string;
+
+use chess_for_crabs::*;
+use game::Game;
+use moves::AlgebraicMove;
+
+fn play(moves: &str) -> Game {
+    let mut game = Game::new();
+    for move_str in moves.split_whitespace() {
+        let alg = AlgebraicMove::parse(move_str).unwrap();
+        let mv = game.board.is_legal(&alg).unwrap();
+        game.make_move(&alg, &mv);
+        assert_eq!(
+            game.board.hash,
+            game.board.compute_hash(),
+            "Hash mismatch after {move_str} in {}",
+            game.board.fen()
+        );
+    }
+    game
+}
+
+#[test]
+fn incremental_hash_matches_full_recomputation() {
+    for game_no in 1..=100 {
+        let moves = read_to_string(format!("games/game_{game_no}.pgn")).unwrap();
+        play(&moves);
+    }
+}
+
+#[test]
+fn transpositions_hash_equal() {
+    let a = play("Nf3 Nf6 Nc3 Nc6");
+    let b = play("Nc3 Nc6 Nf3 Nf6");
+    assert_eq!(a.board.hash, b.board.hash);
+    // Knights out and back in again is the initial position
+    let c = play("Nf3 Nf6 Ng1 Ng8");
+    assert_eq!(c.board.hash, Game::new().board.hash);
+    // Same pieces, different side to move
+    let mut flipped = c.board;
+    flipped.player = flipped.player.opponent();
+    assert_ne!(flipped.compute_hash(), c.board.hash);
+}
+
+#[test]
+fn fen_hash_matches_played_hash() {
+    let game = play("e4 c5 Nf3");
+    let parsed = fen::parse(&game.board.fen().to_string()).unwrap();
+    assert_eq!(game.board.hash, parsed.board.hash);
+}
+
+#[test]
+fn null_move_hash_matches_full_recomputation() {
+    // Including the en passant square going away
+    let game = play("e4 c5 e5 d5");
+    let passed = game.board.apply_null();
+    assert!(passed.player == game.board.player.opponent());
+    assert_eq!(passed.hash, passed.compute_hash());
+    assert_eq!(passed.apply_null().hash, passed.apply_null().compute_hash());
+}